@@ -0,0 +1,56 @@
+//! Compares the backends of the lazy comprehensions on a tiny comprehension
+//! created and consumed in a hot loop: `cargo bench --bench lazy`.
+
+use list_comprehension::{comp, iter_comp, lazy_comp, let_lazy_comp};
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ROUNDS: u32 = 100_000;
+
+fn bench(name: &str, mut f: impl FnMut(u32) -> u32) {
+    // warm up
+    for n in 0..ROUNDS / 10 {
+        black_box(f(black_box(n)));
+    }
+
+    let start = Instant::now();
+    for n in 0..ROUNDS {
+        black_box(f(black_box(n)));
+    }
+    let elapsed: Duration = start.elapsed();
+
+    println!(
+        "{name:<12} {:>8.1} ns/iter",
+        elapsed.as_nanos() as f64 / ROUNDS as f64
+    );
+}
+
+fn main() {
+    bench("comp", |n| {
+        comp![x * y, x in 0..8, y in 0..8, (x + y + n) % 3 == 0]
+            .iter()
+            .sum()
+    });
+
+    bench("iter_comp", |n| {
+        iter_comp![x * y, x in 0..8, y in 0..8, (x + y + n) % 3 == 0].sum()
+    });
+
+    bench("lazy sync", |n| {
+        lazy_comp![x * y, x in 0..8, y in 0..8, (x + y + n) % 3 == 0]
+            .into_iter()
+            .sum()
+    });
+
+    bench("lazy rc", |n| {
+        lazy_comp![rc: x * y, x in 0..8, y in 0..8, (x + y + n) % 3 == 0]
+            .into_iter()
+            .sum()
+    });
+
+    bench("lazy stack", |n| {
+        let_lazy_comp!(gen = [x * y, x in 0..8, y in 0..8, (x + y + n) % 3 == 0]);
+        gen.into_iter().sum()
+    });
+}
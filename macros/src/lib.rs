@@ -0,0 +1,828 @@
+//! The qualifier parser of [`list_comprehension`](https://docs.rs/list_comprehension).
+//! It's an implementation detail, use the macros of `list_comprehension` instead.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Delimiter, Group, Spacing, Span, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, ToTokens};
+use syn::parse::discouraged::Speculative;
+use syn::parse::{Parse, ParseStream};
+use syn::{braced, token, BinOp, Block, Expr, Ident, Member, Pat, Result, Token, Type};
+
+/// `normalize!(Callback Ctx; Qual1, ..., Qualn)` parses the qualifiers and expands to
+/// `Callback!(Ctx; Qual1, ..., Qualn)`, with every qualifier rewritten in the form
+/// the `macro_rules!` backends match without ambiguity. `Callback` is the path of
+/// `parse`, `iter_parse` or `par_comp`, which decides the qualifiers it supports:
+///
+/// * generators become `Ident in Exp` or `for Pattern in Exp`, and stream generators,
+///   `for await Pattern in Exp` or `Pattern in Exp.await_each`, become `for await Pattern in Exp`
+/// * guards become `(Exp)`
+/// * `join Pattern in Exp on Exp == Exp` becomes a `join_index` qualifier, which
+///   builds the index, hoisted in front of the qualifiers, and a `join` qualifier
+///   probing it in place
+/// * `sort by Exp`, `group by Exp` and `drop Exp` become `sort`, `group` and `drop`
+///   qualifiers, which take the variables bound so far and the qualifiers before
+///   them in braces, e.g. `sort (x, y) (Exp) { x in xs, y in ys }`
+/// * `let { Decl1; ...; Decln }` becomes `let Decl1, ..., let Decln`
+/// * `let`, `take`, `while`, `break` and `zip` are checked and passed through
+#[proc_macro]
+pub fn normalize(input: TokenStream) -> TokenStream {
+    match syn::parse::<Normalize>(input) {
+        Ok(normalize) => normalize.into_token_stream().into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct Normalize {
+    callback: TokenStream2,
+    ctx: TokenTree,
+    quals: Vec<TokenStream2>,
+}
+
+impl Parse for Normalize {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut callback = TokenStream2::new();
+        let mut name = None;
+        while !input.peek(token::Bracket) && !input.peek(token::Brace) {
+            let token = input.parse::<TokenTree>()?;
+            if let TokenTree::Ident(ident) = &token {
+                name = Some(ident.clone());
+            }
+            callback.extend([token]);
+        }
+
+        // the callback is the last segment of the path, e.g. `$crate::parse`
+        let backend = match name.map(|name| name.to_string()).as_deref() {
+            Some("parse") => Backend::Parse,
+            Some("iter_parse") => Backend::Iter,
+            Some("par_comp") => Backend::Par,
+            _ => {
+                let msg = "expected `parse`, `iter_parse` or `par_comp` as the callback";
+                return Err(syn::Error::new_spanned(callback, msg));
+            }
+        };
+
+        let ctx = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        let mut parser = Parser {
+            backend,
+            joins: Vec::new(),
+            bound: Vec::new(),
+        };
+        let quals = parser.parse_quals(input, false)?;
+
+        Ok(Normalize {
+            callback,
+            ctx,
+            quals: parser.joins.into_iter().chain(quals).collect(),
+        })
+    }
+}
+
+impl ToTokens for Normalize {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let Normalize {
+            callback,
+            ctx,
+            quals,
+        } = self;
+
+        tokens.extend(quote!(#callback!(#ctx; #( #quals ),*)));
+    }
+}
+
+/// `fair!($crate Callback Ctx; Qual1, ..., Qualn)` merges the generators into a
+/// single generator over `$crate::fair::diagonal`, placed where the first of them
+/// was, and expands to `Callback!(Ctx; Qual1, ..., Qualn)`. The other qualifiers
+/// are passed through unchanged, for `Callback` to normalize.
+#[proc_macro]
+pub fn fair(input: TokenStream) -> TokenStream {
+    match syn::parse::<Fair>(input) {
+        Ok(fair) => fair.into_token_stream().into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct Fair {
+    callback: TokenStream2,
+    ctx: TokenTree,
+    quals: Vec<TokenStream2>,
+}
+
+impl Parse for Fair {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let krate: TokenTree = input.parse()?;
+        let mut callback = TokenStream2::new();
+        while !input.peek(token::Bracket) && !input.peek(token::Brace) {
+            callback.extend([input.parse::<TokenTree>()?]);
+        }
+
+        let ctx = input.parse()?;
+        input.parse::<Token![;]>()?;
+
+        let mut parser = Parser {
+            backend: Backend::Parse,
+            joins: Vec::new(),
+            bound: Vec::new(),
+        };
+        let mut quals = Vec::new();
+        let mut gens = Vec::new();
+        let mut first = None;
+        let mut after_comma = false;
+
+        while !input.is_empty() {
+            if peek_generator(input) {
+                let gen: Generator = input.parse()?;
+                if let Some(await_token) = gen.await_token {
+                    return Err(syn::Error::new_spanned(
+                        await_token,
+                        "stream generators can't be enumerated fairly",
+                    ));
+                }
+
+                if uses(&gen.expr, &parser.bound) {
+                    return Err(syn::Error::new_spanned(
+                        &gen.expr,
+                        "the generators of a fair comprehension can't use the variables bound before them",
+                    ));
+                }
+
+                parser.bind(&gen.pat);
+                first.get_or_insert(quals.len());
+                gens.push(gen);
+            } else if input.peek(Token![while]) {
+                return Err(input.error("`while` can't be used in a fair comprehension"));
+            } else {
+                let begin = input.fork();
+                if peek_transform(input) {
+                    parser.parse_transform(input, Vec::new())?;
+                } else {
+                    parser.parse_qual(input, after_comma)?;
+                }
+                quals.push(tokens_between(&begin, input));
+            }
+
+            if input.is_empty() {
+                break;
+            }
+
+            if !input.peek(Token![,]) {
+                return Err(input.error("expected `,` after a qualifier"));
+            }
+
+            input.parse::<Token![,]>()?;
+            after_comma = true;
+        }
+
+        if let Some(first) = first {
+            let last = gens.pop().unwrap();
+            let (pat, expr) = gens.into_iter().rev().fold(
+                (last.pat.into_token_stream(), last.expr.into_token_stream()),
+                |(pat, expr), gen| {
+                    let Generator {
+                        pat: gen_pat,
+                        expr: gen_expr,
+                        ..
+                    } = gen;
+                    (
+                        quote!((#gen_pat, #pat)),
+                        quote!(#krate::fair::diagonal(#gen_expr, #expr)),
+                    )
+                },
+            );
+            quals.insert(first, quote!(for #pat in #expr));
+        }
+
+        Ok(Fair {
+            callback,
+            ctx,
+            quals,
+        })
+    }
+}
+
+impl ToTokens for Fair {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let Fair {
+            callback,
+            ctx,
+            quals,
+        } = self;
+
+        tokens.extend(quote!(#callback!(#ctx; #( #quals ),*)));
+    }
+}
+
+/// The backend named by the callback of `normalize`.
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    Parse,
+    Iter,
+    Par,
+}
+
+struct Parser {
+    backend: Backend,
+    /// The `join_index` qualifiers, which go in front of all the others.
+    joins: Vec<TokenStream2>,
+    /// The variables bound by the qualifiers parsed so far.
+    bound: Vec<Ident>,
+}
+
+impl Parser {
+    /// `Qual1 , . . . , Qualn` with an optional trailing comma.
+    fn parse_quals(
+        &mut self,
+        input: ParseStream,
+        mut after_comma: bool,
+    ) -> Result<Vec<TokenStream2>> {
+        let mut quals = Vec::new();
+
+        while !input.is_empty() {
+            if peek_transform(input) {
+                let parsed = std::mem::take(&mut quals);
+                quals.push(self.parse_transform(input, parsed)?);
+            } else {
+                let qual = self.parse_qual(input, after_comma)?;
+                // an empty `let {}` has nothing to declare
+                if !qual.is_empty() {
+                    quals.push(qual);
+                }
+            }
+
+            if input.is_empty() {
+                break;
+            }
+
+            if !input.peek(Token![,]) {
+                return Err(input.error("expected `,` after a qualifier"));
+            }
+
+            input.parse::<Token![,]>()?;
+            after_comma = true;
+        }
+
+        Ok(quals)
+    }
+
+    fn parse_qual(&mut self, input: ParseStream, after_comma: bool) -> Result<TokenStream2> {
+        if input.peek(Token![let]) {
+            let mut pats = Vec::new();
+            let tokens = parse_let(input, &mut pats)?;
+            pats.iter().for_each(|pat| self.bind(pat));
+            return Ok(tokens);
+        }
+
+        if input.peek(Token![while]) {
+            let while_token: Token![while] = input.parse()?;
+            self.check_termination(while_token, "while")?;
+            let cond: Expr = input.parse()?;
+            return Ok(quote!(#while_token #cond));
+        }
+
+        if input.peek(Token![break]) {
+            let break_token: Token![break] = input.parse()?;
+            self.check_termination(break_token, "break")?;
+            if input.peek(Token![if]) {
+                let if_token: Token![if] = input.parse()?;
+                let cond: Expr = input.parse()?;
+                return Ok(quote!(#break_token #if_token #cond));
+            }
+
+            return Ok(quote!(#break_token));
+        }
+
+        if (peek_keyword(input, "zip") || peek_keyword(input, "zip_eq"))
+            && input.peek2(token::Brace)
+        {
+            return self.parse_zip(input);
+        }
+
+        if peek_keyword(input, "join") && peek_generator(&skip_keyword(input)) {
+            return self.parse_join(input);
+        }
+
+        if peek_keyword(input, "take") {
+            // `take` is also a valid guard, e.g. `take && x > 0`
+            let fork = input.fork();
+            let take: Ident = fork.parse()?;
+            if let Ok(n) = fork.parse::<Expr>() {
+                if fork.is_empty() || fork.peek(Token![,]) {
+                    self.check_termination(&take, "take")?;
+                    input.advance_to(&fork);
+                    return Ok(quote!(#take #n));
+                }
+            }
+        }
+
+        if peek_generator(input) {
+            let gen: Generator = input.parse()?;
+            self.bind(&gen.pat);
+            return Ok(gen.into_token_stream());
+        }
+
+        let msg = if after_comma {
+            "expected generator, let or guard after `,`"
+        } else {
+            "expected generator, let or guard"
+        };
+        let span = input.span();
+        let pred: Expr = input.parse().map_err(|_| syn::Error::new(span, msg))?;
+
+        Ok(quote!((#pred)))
+    }
+
+    /// Fails if the backend can't end the comprehension early, i.e. `iter_comp`,
+    /// whose levels are separate closures, and `par_comp`, whose elements of the
+    /// first generator are handled separately.
+    fn check_termination(&self, token: impl ToTokens, name: &str) -> Result<()> {
+        let backend = match self.backend {
+            Backend::Parse => return Ok(()),
+            Backend::Iter => "iter_comp",
+            Backend::Par => "par_comp",
+        };
+
+        Err(syn::Error::new_spanned(
+            token,
+            format!("`{name}` is not supported by `{backend}`"),
+        ))
+    }
+
+    /// `zip Branches` or `zip_eq Branches`, where each branch starts with a generator
+    /// binding an identifier or a tuple of identifiers.
+    fn parse_zip(&mut self, input: ParseStream) -> Result<TokenStream2> {
+        let zip: Ident = input.parse()?;
+        let mut branches = Vec::new();
+
+        while input.peek(token::Brace) {
+            let content;
+            let brace = braced!(content in input);
+
+            if !peek_generator(&content) {
+                return Err(content.error("expected a generator at the start of a `zip` branch"));
+            }
+
+            let gen: Generator = content.parse()?;
+            if let Some(await_token) = gen.await_token {
+                return Err(syn::Error::new_spanned(
+                    await_token,
+                    "stream generators can't be used in a `zip` branch",
+                ));
+            }
+
+            let pat = &gen.pat;
+            let expr = &gen.expr;
+            let head = match pat {
+                Pat::Ident(ident) if is_plain(ident) => quote!(for #pat in #expr),
+                Pat::Tuple(tuple)
+                    if tuple
+                        .elems
+                        .iter()
+                        .all(|elem| matches!(elem, Pat::Ident(ident) if is_plain(ident))) =>
+                {
+                    quote!(#pat in #expr)
+                }
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        pat,
+                        "a `zip` branch must bind an identifier or a tuple of identifiers",
+                    ))
+                }
+            };
+
+            self.bind(pat);
+            let mut quals = vec![head];
+            if !content.is_empty() {
+                content.parse::<Token![,]>()?;
+                // a branch is evaluated by `iter_comp`
+                let mut parser = Parser {
+                    backend: Backend::Iter,
+                    joins: Vec::new(),
+                    bound: Vec::new(),
+                };
+                quals.extend(parser.parse_quals(&content, true)?);
+            }
+
+            let mut branch = Group::new(Delimiter::Brace, quote!(#( #quals ),*));
+            branch.set_span(brace.span.join());
+            branches.push(branch);
+        }
+
+        if branches.is_empty() {
+            return Err(input.error(format!("expected `{{` after `{zip}`")));
+        }
+
+        Ok(quote!(#zip #( #branches )*))
+    }
+
+    /// `join Pattern in Exp on Exp == Exp`, where one side of `==` uses the variables
+    /// bound by `Pattern` and the other doesn't.
+    fn parse_join(&mut self, input: ParseStream) -> Result<TokenStream2> {
+        let join: Ident = input.parse()?;
+        let gen: Generator = input.parse()?;
+
+        if !peek_keyword(input, "on") {
+            return Err(input.error("expected `on` after the generator of `join`"));
+        }
+        input.parse::<Ident>()?;
+        let cond: Expr = input.parse()?;
+
+        let backend = match self.backend {
+            Backend::Parse => None,
+            Backend::Iter => Some("iter_comp"),
+            // the index would be built again for every element of the first generator
+            Backend::Par => Some("par_comp"),
+        };
+        if let Some(backend) = backend {
+            return Err(syn::Error::new_spanned(
+                join,
+                format!("`join` is not supported by `{backend}`"),
+            ));
+        }
+
+        if gen.await_token.is_some() {
+            return Err(syn::Error::new_spanned(
+                join,
+                "stream generators can't be joined",
+            ));
+        }
+
+        // the index is built before the loops
+        if uses(&gen.expr, &self.bound) {
+            return Err(syn::Error::new_spanned(
+                &gen.expr,
+                "the generator of `join` can't use the variables bound before it",
+            ));
+        }
+
+        let (lhs, rhs) = match &cond {
+            Expr::Binary(bin) if matches!(bin.op, BinOp::Eq(_)) => (&*bin.left, &*bin.right),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    cond,
+                    "the condition of `join` must be `Exp == Exp`",
+                ))
+            }
+        };
+
+        let mut bound = Vec::new();
+        pat_bindings(&gen.pat, &mut bound);
+        let (outer, inner) = match (uses(lhs, &bound), uses(rhs, &bound)) {
+            (false, true) => (lhs, rhs),
+            (true, false) => (rhs, lhs),
+            _ => return Err(syn::Error::new_spanned(
+                cond,
+                "one side of `==` must use the variables bound by `join`, and the other must not",
+            )),
+        };
+
+        // the key of the index is hoisted with it, where only the variables of `join` are bound
+        let before: Vec<Ident> = self
+            .bound
+            .iter()
+            .filter(|var| !bound.contains(var))
+            .cloned()
+            .collect();
+        if uses(inner, &before) {
+            return Err(syn::Error::new_spanned(
+                inner,
+                "the side of `==` using the variables bound by `join` can't use the variables bound before it",
+            ));
+        }
+        self.bind(&gen.pat);
+
+        let index = Ident::new(
+            &format!("join_index_{}", self.joins.len()),
+            Span::mixed_site(),
+        );
+        let Generator { pat, expr, .. } = &gen;
+        self.joins
+            .push(quote!(join_index (#index, #pat in #expr) (#inner)));
+
+        Ok(quote!(#join (#index, #pat) (#outer)))
+    }
+
+    /// `sort by Exp`, `group by Exp` or `drop Exp`, applied to the bindings of the
+    /// qualifiers `parsed` before it.
+    fn parse_transform(
+        &mut self,
+        input: ParseStream,
+        parsed: Vec<TokenStream2>,
+    ) -> Result<TokenStream2> {
+        let transform: Ident = input.parse()?;
+        if transform != "drop" {
+            input.parse::<Ident>()?;
+        }
+        let arg: Expr = input.parse()?;
+
+        let backend = match self.backend {
+            Backend::Parse => None,
+            Backend::Iter => Some("iter_comp"),
+            Backend::Par => Some("par_comp"),
+        };
+        if let Some(backend) = backend {
+            return Err(syn::Error::new_spanned(
+                transform,
+                format!("transform qualifiers are not supported by `{backend}`"),
+            ));
+        }
+
+        if transform == "drop" {
+            return Ok(quote!(#transform (#arg) { #( #parsed ),* }));
+        }
+
+        let bound = &self.bound;
+        Ok(quote!(#transform (#( #bound ),*) (#arg) { #( #parsed ),* }))
+    }
+
+    /// Records the variables bound by a pattern, skipping the capitalized
+    /// identifiers, which name unit structs, variants or constants.
+    fn bind(&mut self, pat: &Pat) {
+        let mut bound = Vec::new();
+        pat_bindings(pat, &mut bound);
+        for ident in bound {
+            let capitalized = ident
+                .to_string()
+                .trim_start_matches("r#")
+                .starts_with(char::is_uppercase);
+            if !capitalized && !self.bound.contains(&ident) {
+                self.bound.push(ident);
+            }
+        }
+    }
+}
+
+/// `sort by`, `group by` or `drop Exp`, where `drop` isn't the start of a guard.
+fn peek_transform(input: ParseStream) -> bool {
+    if (peek_keyword(input, "sort") || peek_keyword(input, "group"))
+        && peek_keyword(&skip_keyword(input), "by")
+    {
+        return true;
+    }
+
+    let fork = input.fork();
+    peek_keyword(&fork, "drop")
+        && fork.parse::<Ident>().is_ok()
+        && fork.parse::<Expr>().is_ok()
+        && (fork.is_empty() || fork.peek(Token![,]))
+}
+
+fn peek_keyword(input: ParseStream, keyword: &str) -> bool {
+    input
+        .fork()
+        .parse::<Ident>()
+        .is_ok_and(|ident| ident == keyword)
+}
+
+/// A guard can't be followed by `in` or `<-`, so a qualifier is a generator
+/// exactly when a pattern followed by one of them can be parsed.
+fn peek_generator(input: ParseStream) -> bool {
+    if input.peek(Token![for]) {
+        return true;
+    }
+
+    let fork = input.fork();
+    match Pat::parse_multi_with_leading_vert(&fork) {
+        Ok(Pat::Ident(_)) if fork.peek(Token![<-]) => true,
+        Ok(_) => fork.peek(Token![in]),
+        Err(_) => false,
+    }
+}
+
+/// `(for)? Pattern in Exp`, `Ident <- Exp`, `for await Pattern in Exp` or `Pattern in Exp.await_each`.
+struct Generator {
+    await_token: Option<Token![await]>,
+    pat: Pat,
+    expr: Expr,
+}
+
+impl Parse for Generator {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let for_token: Option<Token![for]> = input.parse()?;
+        let await_token = match for_token {
+            Some(_) => input.parse()?,
+            None => None,
+        };
+        let pat = Pat::parse_multi_with_leading_vert(input)?;
+
+        if for_token.is_none() && matches!(pat, Pat::Ident(_)) && input.peek(Token![<-]) {
+            input.parse::<Token![<-]>()?;
+        } else {
+            input.parse::<Token![in]>()?;
+        }
+
+        let mut expr = input.parse()?;
+        let mut await_token = await_token;
+        // `Pattern in Exp.await_each` is the `stream_comp` spelling of `for await Pattern in Exp`
+        if let Expr::Field(field) = &expr {
+            if let Member::Named(member) = &field.member {
+                if await_token.is_none() && member == "await_each" {
+                    await_token = Some(Token![await](member.span()));
+                    expr = (*field.base).clone();
+                }
+            }
+        }
+
+        Ok(Generator {
+            await_token,
+            pat,
+            expr,
+        })
+    }
+}
+
+impl ToTokens for Generator {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let Generator {
+            await_token,
+            pat,
+            expr,
+        } = self;
+
+        match pat {
+            Pat::Ident(ident) if await_token.is_none() && is_plain(ident) => {
+                tokens.extend(quote!(#pat in #expr))
+            }
+            _ => tokens.extend(quote!(for #await_token #pat in #expr)),
+        }
+    }
+}
+
+/// `let Decl` or `let DeclWithElse`, passed through unchanged, or `let (mut)? Decls`,
+/// flattened into one `let Decl` qualifier per declaration (none if it's empty).
+/// The patterns it binds are added to `pats`.
+fn parse_let(input: ParseStream, pats: &mut Vec<Pat>) -> Result<TokenStream2> {
+    let begin = input.fork();
+    let let_token: Token![let] = input.parse()?;
+
+    if input.peek(token::Brace) || (input.peek(Token![mut]) && input.peek2(token::Brace)) {
+        let all_mut: Option<Token![mut]> = input.parse()?;
+        let content;
+        braced!(content in input);
+
+        let mut decls = Vec::new();
+        while !content.is_empty() {
+            let mut decl: Decl = content.parse()?;
+            if all_mut.is_some() {
+                decl.make_mut();
+            }
+            pats.push(decl.pat.clone());
+            decls.push(quote!(#let_token #decl));
+
+            if !content.is_empty() {
+                content.parse::<Token![;]>()?;
+            }
+        }
+
+        return Ok(quote!(#( #decls ),*));
+    }
+
+    let decl: Decl = input.parse()?;
+    pats.push(decl.pat);
+
+    Ok(tokens_between(&begin, input))
+}
+
+/// `(mut)? Pattern ( : Type )? = Exp`, followed by `else { ... }` like a `let`
+/// statement, or by `, else { ... }`.
+struct Decl {
+    pat: Pat,
+    ty: Option<(Token![:], Type)>,
+    expr: Expr,
+    diverge: Option<Block>,
+}
+
+impl Decl {
+    /// Adds `mut` to a declaration that binds a plain identifier.
+    fn make_mut(&mut self) {
+        if let Pat::Ident(ident) = &mut self.pat {
+            if ident.by_ref.is_none() && ident.subpat.is_none() && ident.mutability.is_none() {
+                ident.mutability = Some(Token![mut](ident.ident.span()));
+            }
+        }
+    }
+}
+
+impl Parse for Decl {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let pat = Pat::parse_multi_with_leading_vert(input)?;
+        let ty = if input.peek(Token![:]) {
+            Some((input.parse()?, input.parse()?))
+        } else {
+            None
+        };
+        input.parse::<Token![=]>()?;
+        let expr = input.parse()?;
+
+        let diverge = if input.peek(Token![else]) {
+            input.parse::<Token![else]>()?;
+            Some(input.parse()?)
+        } else if input.peek(Token![,]) && input.peek2(Token![else]) {
+            input.parse::<Token![,]>()?;
+            input.parse::<Token![else]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+
+        Ok(Decl {
+            pat,
+            ty,
+            expr,
+            diverge,
+        })
+    }
+}
+
+impl ToTokens for Decl {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        let Decl {
+            pat,
+            ty,
+            expr,
+            diverge,
+        } = self;
+
+        let ty = ty.as_ref().map(|(colon, ty)| quote!(#colon #ty));
+        let diverge = diverge.as_ref().map(|block| quote!(else #block));
+        tokens.extend(quote!(#pat #ty = #expr #diverge));
+    }
+}
+
+fn skip_keyword<'a>(input: ParseStream<'a>) -> syn::parse::ParseBuffer<'a> {
+    let fork = input.fork();
+    let _ = fork.parse::<Ident>();
+    fork
+}
+
+/// Collects the identifiers bound by a pattern.
+fn pat_bindings(pat: &Pat, bound: &mut Vec<Ident>) {
+    match pat {
+        Pat::Ident(ident) => {
+            bound.push(ident.ident.clone());
+            if let Some((_, subpat)) = &ident.subpat {
+                pat_bindings(subpat, bound);
+            }
+        }
+        Pat::Or(or) => or.cases.iter().for_each(|pat| pat_bindings(pat, bound)),
+        Pat::Paren(paren) => pat_bindings(&paren.pat, bound),
+        Pat::Reference(reference) => pat_bindings(&reference.pat, bound),
+        Pat::Slice(slice) => slice.elems.iter().for_each(|pat| pat_bindings(pat, bound)),
+        Pat::Struct(strukt) => strukt
+            .fields
+            .iter()
+            .for_each(|field| pat_bindings(&field.pat, bound)),
+        Pat::Tuple(tuple) => tuple.elems.iter().for_each(|pat| pat_bindings(pat, bound)),
+        Pat::TupleStruct(tuple) => tuple.elems.iter().for_each(|pat| pat_bindings(pat, bound)),
+        Pat::Type(typed) => pat_bindings(&typed.pat, bound),
+        _ => {}
+    }
+}
+
+/// Whether an expression mentions one of the identifiers, ignoring field and
+/// method names.
+fn uses(expr: &Expr, bound: &[Ident]) -> bool {
+    fn scan(tokens: TokenStream2, bound: &[Ident]) -> bool {
+        // `after_dot` is only set by a single `.`, not by the `..` of a range
+        let mut after_dot = false;
+        let mut after_joint_dot = false;
+        for token in tokens {
+            match &token {
+                TokenTree::Group(group) if scan(group.stream(), bound) => return true,
+                TokenTree::Ident(ident) if !after_dot && bound.contains(ident) => return true,
+                _ => {}
+            }
+
+            let dot = match &token {
+                TokenTree::Punct(punct) if punct.as_char() == '.' => Some(punct.spacing()),
+                _ => None,
+            };
+            after_dot = dot == Some(Spacing::Alone) && !after_joint_dot;
+            after_joint_dot = dot == Some(Spacing::Joint);
+        }
+
+        false
+    }
+
+    scan(expr.to_token_stream(), bound)
+}
+
+fn is_plain(ident: &syn::PatIdent) -> bool {
+    ident.by_ref.is_none() && ident.mutability.is_none() && ident.subpat.is_none()
+}
+
+fn tokens_between(begin: ParseStream, end: ParseStream) -> TokenStream2 {
+    let end = end.cursor();
+    let mut cursor = begin.cursor();
+    let mut tokens = TokenStream2::new();
+
+    while cursor != end {
+        let Some((token, next)) = cursor.token_tree() else {
+            break;
+        };
+        tokens.extend([token]);
+        cursor = next;
+    }
+
+    tokens
+}
@@ -0,0 +1,227 @@
+//! Accumulators used by the aggregate forms of [`crate::comp`].
+//!
+//! `sum` and `product` go through `Sum` and `Product`, so they accept every
+//! type that `Iterator::sum` and `Iterator::product` accept, e.g. `&i32`
+//! items summed into an `i32`. Like those, the result type usually needs an
+//! annotation.
+
+use core::cmp;
+use core::iter::{empty, once, Product, Sum};
+
+pub fn zero<S: Sum<S>>() -> S {
+    empty::<S>().sum()
+}
+
+pub fn add<T, S: Sum<T> + Sum<S>>(acc: S, item: T) -> S {
+    once(acc).chain(once(once(item).sum())).sum()
+}
+
+pub fn one<S: Product<S>>() -> S {
+    empty::<S>().product()
+}
+
+pub fn mul<T, S: Product<T> + Product<S>>(acc: S, item: T) -> S {
+    once(acc).chain(once(once(item).product())).product()
+}
+
+/// Keeps the first minimum, like `Iterator::min`.
+pub fn min<T: Ord>(acc: Option<T>, item: T) -> Option<T> {
+    Some(match acc {
+        Some(acc) => cmp::min(acc, item),
+        None => item,
+    })
+}
+
+/// Keeps the last maximum, like `Iterator::max`.
+pub fn max<T: Ord>(acc: Option<T>, item: T) -> Option<T> {
+    Some(match acc {
+        Some(acc) => cmp::max(acc, item),
+        None => item,
+    })
+}
+
+pub fn min_by_key<T, K, F>(acc: Option<(K, T)>, item: T, f: &mut F) -> Option<(K, T)>
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    let key = f(&item);
+    match acc {
+        Some((acc_key, acc)) if acc_key <= key => Some((acc_key, acc)),
+        _ => Some((key, item)),
+    }
+}
+
+pub fn max_by_key<T, K, F>(acc: Option<(K, T)>, item: T, f: &mut F) -> Option<(K, T)>
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    let key = f(&item);
+    match acc {
+        Some((acc_key, acc)) if acc_key > key => Some((acc_key, acc)),
+        _ => Some((key, item)),
+    }
+}
+
+pub fn fold<A, T, F: FnMut(A, T) -> A>(f: &mut F, acc: A, item: T) -> A {
+    f(acc, item)
+}
+
+/// Lowers the aggregate forms of [`crate::comp`], e.g. `comp![sum: x * y, x in a, y in b]`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! aggregate {
+    // 入口
+    ($mode:ident $args:tt $out:expr => $( $unparsed:tt )+) => {
+        $crate::aggregate!(@$mode $args $out; $( $unparsed )+)
+    };
+
+    ($mode:ident $args:tt $out:expr , $( $unparsed:tt )+) => {
+        $crate::aggregate!(@$mode $args $out; $( $unparsed )+)
+    };
+
+    ($mode:ident $args:tt $out:expr ; $( $unparsed:tt )+) => {
+        $crate::aggregate!(@$mode $args $out; $( $unparsed )+)
+    };
+
+    // 展开
+    (@sum () $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut acc = $crate::aggregate::zero();
+            $crate::parse!({ acc = $crate::aggregate::add(acc, $out); }; $( $unparsed )+);
+
+            acc
+        }
+    };
+
+    (@product () $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut acc = $crate::aggregate::one();
+            $crate::parse!({ acc = $crate::aggregate::mul(acc, $out); }; $( $unparsed )+);
+
+            acc
+        }
+    };
+
+    (@min () $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut acc = ::core::option::Option::None;
+            $crate::parse!({ acc = $crate::aggregate::min(acc, $out); }; $( $unparsed )+);
+
+            acc
+        }
+    };
+
+    (@max () $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut acc = ::core::option::Option::None;
+            $crate::parse!({ acc = $crate::aggregate::max(acc, $out); }; $( $unparsed )+);
+
+            acc
+        }
+    };
+
+    (@min_by_key $args:tt $out:expr; $( $unparsed:tt )+) => {
+        $crate::aggregate!(@by_key min_by_key $args $out; $( $unparsed )+)
+    };
+
+    (@max_by_key $args:tt $out:expr; $( $unparsed:tt )+) => {
+        $crate::aggregate!(@by_key max_by_key $args $out; $( $unparsed )+)
+    };
+
+    // a closure literal is written at the call, so that its parameter types are inferred
+    (@by_key $by_key:ident (| $( $key:tt )*) $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut acc = ::core::option::Option::None;
+            $crate::parse!({
+                acc = $crate::aggregate::$by_key(acc, $out, &mut (| $( $key )*));
+            }; $( $unparsed )+);
+
+            ::core::option::Option::map(acc, |(_, item)| item)
+        }
+    };
+
+    (@by_key $by_key:ident (move $( $key:tt )*) $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut acc = ::core::option::Option::None;
+            $crate::parse!({
+                acc = $crate::aggregate::$by_key(acc, $out, &mut (move $( $key )*));
+            }; $( $unparsed )+);
+
+            ::core::option::Option::map(acc, |(_, item)| item)
+        }
+    };
+
+    (@by_key $by_key:ident ($key:expr) $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut key = $key;
+            let mut acc = ::core::option::Option::None;
+            $crate::parse!({
+                acc = $crate::aggregate::$by_key(acc, $out, &mut key);
+            }; $( $unparsed )+);
+
+            ::core::option::Option::map(acc, |(_, item)| item)
+        }
+    };
+
+    (@any () $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut acc = false;
+            $crate::parse!('aggregate {
+                if $out {
+                    acc = true;
+                    break 'aggregate;
+                }
+            }; $( $unparsed )+);
+
+            acc
+        }
+    };
+
+    (@all () $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut acc = true;
+            $crate::parse!('aggregate {
+                if !($out) {
+                    acc = false;
+                    break 'aggregate;
+                }
+            }; $( $unparsed )+);
+
+            acc
+        }
+    };
+
+    (@fold ($init:expr, | $( $f:tt )*) $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut acc = $init;
+            $crate::parse!({
+                acc = $crate::aggregate::fold(&mut (| $( $f )*), acc, $out);
+            }; $( $unparsed )+);
+
+            acc
+        }
+    };
+
+    (@fold ($init:expr, move $( $f:tt )*) $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut acc = $init;
+            $crate::parse!({
+                acc = $crate::aggregate::fold(&mut (move $( $f )*), acc, $out);
+            }; $( $unparsed )+);
+
+            acc
+        }
+    };
+
+    (@fold ($init:expr, $f:expr) $out:expr; $( $unparsed:tt )+) => {
+        {
+            let mut f = $f;
+            let mut acc = $init;
+            $crate::parse!({ acc = $crate::aggregate::fold(&mut f, acc, $out); }; $( $unparsed )+);
+
+            acc
+        }
+    };
+}
@@ -0,0 +1,268 @@
+//! Fixed-capacity outputs of [`crate::fill_comp`].
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// The error returned when an output doesn't fit in the buffer. It holds
+/// the output that was rejected.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError<T> {
+    element: T,
+}
+
+impl<T> CapacityError<T> {
+    pub fn new(element: T) -> Self {
+        CapacityError { element }
+    }
+
+    /// Returns the output that didn't fit.
+    pub fn element(self) -> T {
+        self.element
+    }
+}
+
+impl<T> fmt::Debug for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CapacityError: insufficient capacity")
+    }
+}
+
+impl<T> fmt::Display for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("insufficient capacity")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> std::error::Error for CapacityError<T> {}
+
+/// Insertion into a buffer that may be full, used by [`crate::fill_comp`].
+///
+/// It is implemented for [`SliceBuf`], for `Vec` with the `alloc` feature,
+/// which never grows past its capacity, and for `arrayvec::ArrayVec` and
+/// `heapless::Vec` with the features of the same name. Implement it for your
+/// own buffer type to fill it.
+pub trait TryPush<T> {
+    /// Appends `item`, or gives it back if the buffer is full.
+    fn try_push(&mut self, item: T) -> Result<(), CapacityError<T>>;
+
+    /// The number of items that can still be pushed.
+    fn remaining(&self) -> usize;
+
+    fn is_full(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
+impl<T, B: TryPush<T> + ?Sized> TryPush<T> for &mut B {
+    fn try_push(&mut self, item: T) -> Result<(), CapacityError<T>> {
+        (**self).try_push(item)
+    }
+
+    fn remaining(&self) -> usize {
+        (**self).remaining()
+    }
+}
+
+/// A fixed-capacity buffer over a caller-supplied slice, such as `[T; N]`.
+/// Pushing overwrites the slots from the start, and the buffer dereferences
+/// to the slots written so far.
+/// # Example
+/// ```rust
+/// use list_comprehension::{SliceBuf, TryPush};
+///
+/// let mut slots = [0; 2];
+/// let mut buf = SliceBuf::new(&mut slots);
+/// assert!(buf.try_push(1).is_ok());
+/// assert!(buf.try_push(2).is_ok());
+/// assert_eq!(buf.try_push(3).unwrap_err().element(), 3);
+/// assert_eq!(*buf, [1, 2]);
+/// ```
+#[derive(Debug)]
+pub struct SliceBuf<'a, T> {
+    slots: &'a mut [T],
+    len: usize,
+}
+
+impl<'a, T> SliceBuf<'a, T> {
+    pub fn new(slots: &'a mut [T]) -> Self {
+        SliceBuf { slots, len: 0 }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == self.slots.len()
+    }
+
+    /// Forgets the written slots, without dropping them.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Returns the slots written so far.
+    pub fn into_slice(self) -> &'a mut [T] {
+        &mut self.slots[..self.len]
+    }
+}
+
+impl<T> Deref for SliceBuf<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.slots[..self.len]
+    }
+}
+
+impl<T> DerefMut for SliceBuf<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.slots[..self.len]
+    }
+}
+
+impl<T> TryPush<T> for SliceBuf<'_, T> {
+    fn try_push(&mut self, item: T) -> Result<(), CapacityError<T>> {
+        match self.slots.get_mut(self.len) {
+            Some(slot) => {
+                *slot = item;
+                self.len += 1;
+                Ok(())
+            }
+            None => Err(CapacityError::new(item)),
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.slots.len() - self.len
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> TryPush<T> for Vec<T> {
+    fn try_push(&mut self, item: T) -> Result<(), CapacityError<T>> {
+        if self.len() < self.capacity() {
+            self.push(item);
+            Ok(())
+        } else {
+            Err(CapacityError::new(item))
+        }
+    }
+
+    fn remaining(&self) -> usize {
+        self.capacity() - self.len()
+    }
+}
+
+#[cfg(feature = "arrayvec")]
+impl<T, const N: usize> TryPush<T> for arrayvec::ArrayVec<T, N> {
+    fn try_push(&mut self, item: T) -> Result<(), CapacityError<T>> {
+        arrayvec::ArrayVec::try_push(self, item).map_err(|err| CapacityError::new(err.element()))
+    }
+
+    fn remaining(&self) -> usize {
+        self.remaining_capacity()
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<T, const N: usize> TryPush<T> for heapless::Vec<T, N> {
+    fn try_push(&mut self, item: T) -> Result<(), CapacityError<T>> {
+        self.push(item).map_err(CapacityError::new)
+    }
+
+    fn remaining(&self) -> usize {
+        self.capacity() - self.len()
+    }
+}
+
+/// Syntax is the same as [`crate::comp`], except that the outputs are pushed
+/// into a caller-supplied buffer implementing [`TryPush`], such as a
+/// [`SliceBuf`] over an array, so nothing is allocated. A policy prefix with
+/// the buffer decides what happens when it's full:
+/// * `stop(buf):` the comprehension ends right after the push that fills the
+///   buffer, keeping the outputs that fit
+/// * `err(buf):` the comprehension ends and returns `Err(CapacityError)` with
+///   the first output that doesn't fit, it returns `Ok(())` if every output fits
+/// * `panic(buf):` the comprehension panics at the first output that doesn't fit
+///
+/// `err` and `panic` can only tell that an output doesn't fit once they reach
+/// it, so they don't end while the generators keep yielding bindings that are
+/// filtered out.
+/// # Example
+/// ```rust
+/// use list_comprehension::{fill_comp, CapacityError, SliceBuf};
+///
+/// let mut slots = [0; 4];
+/// let mut buf = SliceBuf::new(&mut slots);
+/// fill_comp![stop(buf): n * n, n in 1.., n % 2 == 1];
+/// assert_eq!(*buf, [1, 9, 25, 49]);
+///
+/// let mut slots = [0; 2];
+/// let mut buf = SliceBuf::new(&mut slots);
+/// let res = fill_comp![err(buf): n, n in 0..3];
+/// assert_eq!(res, Err(CapacityError::new(2)));
+/// assert_eq!(*buf, [0, 1]);
+/// ```
+#[macro_export]
+macro_rules! fill_comp {
+    // 入口
+    ($policy:ident ( $buf:expr ) : $out:expr => $( $unparsed:tt )+) => {
+        $crate::fill_comp!(@$policy ($buf) $out; $( $unparsed )+)
+    };
+
+    ($policy:ident ( $buf:expr ) : $out:expr , $( $unparsed:tt )+) => {
+        $crate::fill_comp!(@$policy ($buf) $out; $( $unparsed )+)
+    };
+
+    ($policy:ident ( $buf:expr ) : $out:expr ; $( $unparsed:tt )+) => {
+        $crate::fill_comp!(@$policy ($buf) $out; $( $unparsed )+)
+    };
+
+    // 展开
+    // `try_push` is called through the trait, since `ArrayVec` has an inherent one,
+    // and a full buffer is noticed right after the push that fills it
+    (@stop ($buf:expr) $out:expr; $( $unparsed:tt )+) => {
+        {
+            use $crate::capacity::TryPush as _;
+            let res = &mut $buf;
+            if !res.is_full() {
+                $crate::parse!('fill {
+                    if $crate::capacity::TryPush::try_push(res, $out).is_err() || res.is_full() {
+                        break 'fill;
+                    }
+                }; $( $unparsed )+);
+            }
+        }
+    };
+
+    (@err ($buf:expr) $out:expr; $( $unparsed:tt )+) => {
+        {
+            let res = &mut $buf;
+            let mut filled = ::core::result::Result::Ok(());
+            $crate::parse!('fill {
+                if let ::core::result::Result::Err(err) = $crate::capacity::TryPush::try_push(res, $out) {
+                    filled = ::core::result::Result::Err(err);
+                    break 'fill;
+                }
+            }; $( $unparsed )+);
+
+            filled
+        }
+    };
+
+    (@panic ($buf:expr) $out:expr; $( $unparsed:tt )+) => {
+        {
+            let res = &mut $buf;
+            $crate::parse!({
+                if $crate::capacity::TryPush::try_push(res, $out).is_err() {
+                    ::core::panic!("the buffer of `fill_comp` is full");
+                }
+            }; $( $unparsed )+);
+        }
+    };
+}
@@ -0,0 +1,108 @@
+//! The enumeration behind the fair mode of [`crate::lazy_comp`], e.g.
+//! `lazy_comp![fair: (x, y), x in 0.., y in 0..]`.
+
+use alloc::vec::Vec;
+
+/// Pairs every item of `a` with every item of `b`, going through the pairs
+/// diagonally, in Cantor's order: `(a0, b0), (a0, b1), (a1, b0), (a0, b2), ...`.
+/// Every pair is reached after a finite number of steps, even if both
+/// iterators are infinite.
+///
+/// The items are cached the first time they are needed, and cloned for every
+/// pair they are part of.
+pub fn diagonal<A, B>(a: A, b: B) -> Diagonal<A::IntoIter, B::IntoIter>
+where
+    A: IntoIterator,
+    B: IntoIterator,
+    A::Item: Clone,
+    B::Item: Clone,
+{
+    Diagonal {
+        a: Source::new(a.into_iter()),
+        b: Source::new(b.into_iter()),
+        sum: 0,
+        i: 0,
+    }
+}
+
+pub struct Diagonal<A: Iterator, B: Iterator> {
+    a: Source<A>,
+    b: Source<B>,
+    /// The pairs `(i, j)` of the current diagonal have `i + j == sum`.
+    sum: usize,
+    i: usize,
+}
+
+impl<A, B> Iterator for Diagonal<A, B>
+where
+    A: Iterator,
+    B: Iterator,
+    A::Item: Clone,
+    B::Item: Clone,
+{
+    type Item = (A::Item, B::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.i > self.sum {
+                self.sum += 1;
+                self.i = 0;
+            }
+
+            if self.a.done && self.b.done && self.sum + 2 > self.a.items.len() + self.b.items.len()
+            {
+                return None;
+            }
+
+            let (i, j) = (self.i, self.sum - self.i);
+            if !self.a.fill(i) {
+                if self.a.items.is_empty() {
+                    return None;
+                }
+
+                // the rest of the diagonal is past the end of `a` too
+                self.i = self.sum + 1;
+                continue;
+            }
+
+            self.i += 1;
+            if !self.b.fill(j) {
+                if self.b.items.is_empty() {
+                    return None;
+                }
+
+                continue;
+            }
+
+            return Some((self.a.items[i].clone(), self.b.items[j].clone()));
+        }
+    }
+}
+
+struct Source<I: Iterator> {
+    iter: I,
+    items: Vec<I::Item>,
+    done: bool,
+}
+
+impl<I: Iterator> Source<I> {
+    fn new(iter: I) -> Self {
+        Source {
+            iter,
+            items: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Pulls items until the one at `index` is cached, returns whether it exists.
+    fn fill(&mut self, index: usize) -> bool {
+        while !self.done && self.items.len() <= index {
+            match self.iter.next() {
+                Some(item) => self.items.push(item),
+                None => self.done = true,
+            }
+        }
+
+        index < self.items.len()
+    }
+}
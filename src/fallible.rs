@@ -0,0 +1,81 @@
+/// Wraps the result of a successful [`crate::try_comp`].
+///
+/// It is implemented for `Result<T, E>` and `Option<T>`, so `try_comp!`
+/// returns whichever of them the context asks for.
+pub trait FromOutput<T> {
+    fn from_output(output: T) -> Self;
+}
+
+impl<T, E> FromOutput<T> for Result<T, E> {
+    fn from_output(output: T) -> Self {
+        Ok(output)
+    }
+}
+
+impl<T> FromOutput<T> for Option<T> {
+    fn from_output(output: T) -> Self {
+        Some(output)
+    }
+}
+
+#[doc(hidden)]
+pub fn run<R, F: FnOnce() -> R>(f: F) -> R {
+    f()
+}
+
+/// Syntax is the same as [`crate::comp`], except that `?` in the output,
+/// guards and local declarations stops the comprehension and returns the
+/// error, instead of returning from the enclosing function.
+///
+/// It returns `Result<Vec<T>, E>` or `Option<Vec<T>>`, depending on the
+/// context, so it usually needs a type annotation.
+/// # Example
+/// ```rust
+/// use list_comprehension::try_comp;
+/// use std::num::ParseIntError;
+///
+/// let ok: Result<Vec<i32>, ParseIntError> = try_comp![
+///     n * 2
+///     , s in ["1", "2", "3"]
+///     , let n = s.parse::<i32>()?
+/// ];
+/// assert_eq!(ok, Ok(vec![2, 4, 6]));
+///
+/// let err: Result<Vec<i32>, ParseIntError> = try_comp![
+///     s.parse::<i32>()? * 2
+///     , s in ["1", "x", "3"]
+/// ];
+/// assert!(err.is_err());
+///
+/// let none: Option<Vec<i32>> = try_comp![x?, x in [Some(1), None]];
+/// assert_eq!(none, None);
+/// ```
+#[macro_export]
+macro_rules! try_comp {
+    ($out:expr => $( $unparsed:tt )+) => {
+        $crate::fallible::run(|| {
+            let mut res = $crate::__private::Vec::new();
+            $crate::parse!({ res.push($out); }; $( $unparsed )+);
+
+            $crate::fallible::FromOutput::from_output(res)
+        })
+    };
+
+    ($out:expr , $( $unparsed:tt )+) => {
+        $crate::fallible::run(|| {
+            let mut res = $crate::__private::Vec::new();
+            $crate::parse!({ res.push($out); }; $( $unparsed )+);
+
+            $crate::fallible::FromOutput::from_output(res)
+        })
+    };
+
+    ($out:expr ; $( $unparsed:tt )+) => {
+        $crate::fallible::run(|| {
+            let mut res = $crate::__private::Vec::new();
+            $crate::parse!({ res.push($out); }; $( $unparsed )+);
+
+            $crate::fallible::FromOutput::from_output(res)
+        })
+    };
+}
@@ -0,0 +1,220 @@
+/// Syntax is the same as [`crate::comp`], except that it's lazy and needs no
+/// dependency: the qualifiers are lowered into nested `flat_map` adapters,
+/// so it returns a plain `impl Iterator` (which is also `Clone` if all the
+/// captures are).
+///
+/// Every level of the comprehension is a `move` closure, so a binding used by
+/// an inner generator, guard or the output has to be `Copy` (or cloned
+/// explicitly), and collections captured from the environment should be
+/// borrowed beforehand (`let xs = &xs;`).
+///
+/// `take`, `while` and `break` aren't supported, use the adapters of the
+/// returned iterator (`take`, `take_while`) instead:
+/// ```compile_fail
+/// use list_comprehension::iter_comp;
+///
+/// // error: `take` is not supported by `iter_comp`
+/// let it = iter_comp![x, x in 0..3, take 2];
+/// ```
+/// # Example
+/// ```rust
+/// use list_comprehension::iter_comp;
+///
+/// fn pythagorean(n: u32) -> impl Iterator<Item = (u32, u32, u32)> + Clone {
+///     iter_comp![
+///         (a, b, c)
+///         , c in 1..=n
+///         , b in 1..c
+///         , a in 1..b
+///         , a * a + b * b == c * c
+///     ]
+/// }
+///
+/// let triples = pythagorean(13);
+/// assert_eq!(triples.clone().next(), Some((3, 4, 5)));
+/// assert_eq!(triples.count(), 3);
+/// ```
+#[macro_export]
+macro_rules! iter_comp {
+    (.. $out:expr => $( $unparsed:tt )+) => {
+        $crate::__private::normalize!($crate::iter_parse { ::core::iter::IntoIterator::into_iter($out) }; $( $unparsed )+)
+    };
+
+    (.. $out:expr , $( $unparsed:tt )+) => {
+        $crate::__private::normalize!($crate::iter_parse { ::core::iter::IntoIterator::into_iter($out) }; $( $unparsed )+)
+    };
+
+    (.. $out:expr ; $( $unparsed:tt )+) => {
+        $crate::__private::normalize!($crate::iter_parse { ::core::iter::IntoIterator::into_iter($out) }; $( $unparsed )+)
+    };
+
+    ($out:expr => $( $unparsed:tt )+) => {
+        $crate::__private::normalize!($crate::iter_parse { ::core::iter::once($out) }; $( $unparsed )+)
+    };
+
+    ($out:expr , $( $unparsed:tt )+) => {
+        $crate::__private::normalize!($crate::iter_parse { ::core::iter::once($out) }; $( $unparsed )+)
+    };
+
+    ($out:expr ; $( $unparsed:tt )+) => {
+        $crate::__private::normalize!($crate::iter_parse { ::core::iter::once($out) }; $( $unparsed )+)
+    };
+}
+
+#[macro_export]
+macro_rules! iter_parse {
+    // 递归展开
+    (
+        $sink:tt;
+        $var:ident in $iter:expr
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        ::core::iter::Iterator::flat_map(
+            ::core::iter::IntoIterator::into_iter($iter),
+            move |$var| $crate::iter_parse!($sink; $($( $unparsed )*)?),
+        )
+    };
+
+    (
+        $sink:tt;
+        $var:ident <- $iter:expr
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        ::core::iter::Iterator::flat_map(
+            ::core::iter::IntoIterator::into_iter($iter),
+            move |$var| $crate::iter_parse!($sink; $($( $unparsed )*)?),
+        )
+    };
+
+    (
+        $sink:tt;
+        let $var:ident $(: $ty:ty)? = $expr:expr
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        {
+            let $var $(: $ty)? = $expr;
+
+            $crate::iter_parse!($sink; $($( $unparsed )*)?)
+        }
+    };
+
+    (
+        $sink:tt;
+        let mut $var:ident $(: $ty:ty)? = $expr:expr
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        {
+            let mut $var $(: $ty)? = $expr;
+
+            $crate::iter_parse!($sink; $($( $unparsed )*)?)
+        }
+    };
+
+    (
+        $sink:tt;
+        let $var:pat = $expr:expr , else { $( $else_code:tt )* }
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        {
+            let $var = $expr else { $( $else_code )* };
+
+            $crate::iter_parse!($sink; $($( $unparsed )*)?)
+        }
+    };
+
+    (
+        $sink:tt;
+        let $var:pat = $expr:expr
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        {
+            let $var = $expr;
+
+            $crate::iter_parse!($sink; $($( $unparsed )*)?)
+        }
+    };
+
+    (
+        $sink:tt;
+        zip $( { $( $branch:tt )* } )+
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        $crate::parallel!(
+            (iter_parse [$sink] zip) [] []; $( { $( $branch )* } )+ ; $($( $unparsed )*)?
+        )
+    };
+
+    (
+        $sink:tt;
+        zip_eq $( { $( $branch:tt )* } )+
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        $crate::parallel!(
+            (iter_parse [$sink] zip_eq) [] []; $( { $( $branch )* } )+ ; $($( $unparsed )*)?
+        )
+    };
+
+    (
+        $sink:tt;
+        for $var:pat in $iter:expr
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        ::core::iter::Iterator::flatten(::core::iter::Iterator::filter_map(
+            ::core::iter::IntoIterator::into_iter($iter),
+            move |item| match item {
+                $var => ::core::option::Option::Some($crate::iter_parse!($sink; $($( $unparsed )*)?)),
+                #[allow(unreachable_patterns)]
+                _ => ::core::option::Option::None,
+            },
+        ))
+    };
+
+    (
+        $sink:tt;
+        $pred:expr
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        ::core::iter::Iterator::flatten(::core::iter::IntoIterator::into_iter(
+            if $pred {
+                ::core::option::Option::Some($crate::iter_parse!($sink; $($( $unparsed )*)?))
+            } else {
+                ::core::option::Option::None
+            }
+        ))
+    };
+
+    (
+        $sink:tt;
+        $let_stmt:stmt
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        {
+            $let_stmt;
+
+            $crate::iter_parse!($sink; $($( $unparsed )*)?)
+        }
+    };
+
+    (
+        $sink:tt;
+        $var:pat in $iter:expr
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        ::core::iter::Iterator::flatten(::core::iter::Iterator::filter_map(
+            ::core::iter::IntoIterator::into_iter($iter),
+            move |item| match item {
+                $var => ::core::option::Option::Some($crate::iter_parse!($sink; $($( $unparsed )*)?)),
+                #[allow(unreachable_patterns)]
+                _ => ::core::option::Option::None,
+            },
+        ))
+    };
+
+
+    // 结束条件
+    (
+        { $( $sink:tt )* };
+    ) => {
+        $( $sink )*
+    };
+}
@@ -0,0 +1,59 @@
+//! The index behind the `join Pattern in Exp on Exp == Exp` qualifier.
+//!
+//! The index is built before the loops, so the joined generator can't use the
+//! variables bound before it:
+//! ```compile_fail
+//! use list_comprehension::comp;
+//!
+//! let xs = [[1, 2], [3, 4]];
+//! // error: the generator of `join` can't use the variables bound before it
+//! let pairs = comp![(x, y), x in xs, join y in x on y == 1];
+//! ```
+//!
+//! Neither can the side of `==` that uses the variables of `join`, which is
+//! the key of the index:
+//! ```compile_fail
+//! use list_comprehension::comp;
+//!
+//! let orders = [(1, 2)];
+//! let customers = [(2, "Ada")];
+//! // error: the side of `==` using the variables bound by `join` can't use the variables bound before it
+//! let pairs = comp![(o, c), o in orders, join c in customers on c.0 + o.0 == 3];
+//! ```
+
+use alloc::vec::Vec;
+use core::hash::Hash;
+use core::iter::Cloned;
+use core::slice::Iter;
+use std::collections::HashMap;
+
+/// Groups the items matching the pattern of `join` by their key, keeping
+/// the order of the generator in each group.
+pub fn index<T, K, I, F>(iter: I, mut key: F) -> HashMap<K, Vec<T>>
+where
+    T: Clone,
+    K: Hash + Eq,
+    I: IntoIterator<Item = T>,
+    F: FnMut(T) -> Option<K>,
+{
+    let mut index: HashMap<K, Vec<T>> = HashMap::new();
+    for item in iter {
+        if let Some(key) = key(item.clone()) {
+            index.entry(key).or_default().push(item);
+        }
+    }
+
+    index
+}
+
+/// The items of the joined generator whose key is `key`, in their original order.
+pub fn probe<'a, T: Clone, K: Hash + Eq>(
+    index: &'a HashMap<K, Vec<T>>,
+    key: &K,
+) -> Cloned<Iter<'a, T>> {
+    index
+        .get(key)
+        .map_or(&[][..], Vec::as_slice)
+        .iter()
+        .cloned()
+}
@@ -1,89 +1,160 @@
-use genawaiter::sync::{Co, Gen};
+use core::future::Future;
 
-use std::future::Future;
-use std::marker::PhantomData;
+/// Defines `LazyComp` and its `IntoIter` over the generators of
+/// `genawaiter::$flavour`, so that the `sync` and `rc` flavours share them.
+/// The invoking module imports `Future`.
+macro_rules! lazy_comp_impl {
+    (
+        $flavour:ident;
+        $( #[$comp_attr:meta] )*
+        pub struct LazyComp;
+        $( #[$iter_attr:meta] )*
+        pub struct IntoIter;
+    ) => {
+        use genawaiter::$flavour::{Co, Gen};
 
-/// Don't bother with his complex declarations, just know
-/// that it implements `Clone` and `IntoIter`.
-pub struct LazyComp<Y, F, Fu>
-where
-    F: FnOnce(Co<Y>) -> Fu,
-    Fu: Future<Output = ()>,
-{
-    func: F,
-    _m: PhantomData<Co<Y>>,
-}
+        use core::marker::PhantomData;
 
-impl<Y, F, Fu> LazyComp<Y, F, Fu>
-where
-    F: FnOnce(Co<Y>) -> Fu,
-    Fu: Future<Output = ()>,
-{
-    pub fn new(f: F) -> LazyComp<Y, F, Fu> {
-        LazyComp {
-            func: f,
-            _m: PhantomData,
+        $( #[$comp_attr] )*
+        pub struct LazyComp<Y, F, Fu>
+        where
+            F: FnOnce(Co<Y>) -> Fu,
+            Fu: Future<Output = ()>,
+        {
+            func: F,
+            _m: PhantomData<Co<Y>>,
         }
-    }
-}
 
-impl<Y, F, Fu> IntoIterator for LazyComp<Y, F, Fu>
-where
-    F: FnOnce(Co<Y>) -> Fu,
-    Fu: Future<Output = ()>,
-{
-    type Item = Y;
-    type IntoIter = IntoIter<Y, Fu>;
+        impl<Y, F, Fu> LazyComp<Y, F, Fu>
+        where
+            F: FnOnce(Co<Y>) -> Fu,
+            Fu: Future<Output = ()>,
+        {
+            pub fn new(f: F) -> LazyComp<Y, F, Fu> {
+                LazyComp {
+                    func: f,
+                    _m: PhantomData,
+                }
+            }
+        }
 
-    fn into_iter(self) -> Self::IntoIter {
-        IntoIter {
-            generator: Gen::new(self.func),
-            completed: false,
+        impl<Y, F, Fu> LazyComp<Y, F, Fu>
+        where
+            F: Clone + FnOnce(Co<Y>) -> Fu,
+            Fu: Future<Output = ()>,
+        {
+            /// Starts the comprehension over again, without consuming it. It runs a
+            /// clone of the closure, and so of the captures.
+            pub fn iter(&self) -> IntoIter<Y, Fu> {
+                IntoIter {
+                    generator: Gen::new(self.func.clone()),
+                    completed: false,
+                }
+            }
         }
-    }
-}
 
-impl<Y, F, Fu> Clone for LazyComp<Y, F, Fu>
-where
-    F: Clone + FnOnce(Co<Y>) -> Fu,
-    Fu: Future<Output = ()>,
-{
-    fn clone(&self) -> Self {
-        LazyComp {
-            func: self.func.clone(),
-            _m: PhantomData,
+        impl<Y, F, Fu> IntoIterator for LazyComp<Y, F, Fu>
+        where
+            F: FnOnce(Co<Y>) -> Fu,
+            Fu: Future<Output = ()>,
+        {
+            type Item = Y;
+            type IntoIter = IntoIter<Y, Fu>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                IntoIter {
+                    generator: Gen::new(self.func),
+                    completed: false,
+                }
+            }
         }
-    }
-}
 
-/// It does not implement `Clone`. Please use ```lazy_comp![].clone().into_iter()```
-pub struct IntoIter<Y, Fu: Future<Output = ()>> {
-    generator: Gen<Y, (), Fu>,
-    completed: bool,
-}
+        impl<Y, F, Fu> IntoIterator for &LazyComp<Y, F, Fu>
+        where
+            F: Clone + FnOnce(Co<Y>) -> Fu,
+            Fu: Future<Output = ()>,
+        {
+            type Item = Y;
+            type IntoIter = IntoIter<Y, Fu>;
 
-impl<Y, Fu: Future<Output = ()>> Iterator for IntoIter<Y, Fu> {
-    type Item = Y;
+            fn into_iter(self) -> Self::IntoIter {
+                self.iter()
+            }
+        }
+
+        impl<Y, F, Fu> Clone for LazyComp<Y, F, Fu>
+        where
+            F: Clone + FnOnce(Co<Y>) -> Fu,
+            Fu: Future<Output = ()>,
+        {
+            fn clone(&self) -> Self {
+                LazyComp {
+                    func: self.func.clone(),
+                    _m: PhantomData,
+                }
+            }
+        }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.completed {
-            return None;
+        $( #[$iter_attr] )*
+        pub struct IntoIter<Y, Fu: Future<Output = ()>> {
+            generator: Gen<Y, (), Fu>,
+            completed: bool,
         }
 
-        match self.generator.resume() {
-            genawaiter::GeneratorState::Yielded(x) => Some(x),
-            genawaiter::GeneratorState::Complete(()) => {
-                self.completed = true;
-                None
+        impl<Y, Fu: Future<Output = ()>> Iterator for IntoIter<Y, Fu> {
+            type Item = Y;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                if self.completed {
+                    return None;
+                }
+
+                match self.generator.resume() {
+                    genawaiter::GeneratorState::Yielded(x) => Some(x),
+                    genawaiter::GeneratorState::Complete(()) => {
+                        self.completed = true;
+                        None
+                    }
+                }
             }
         }
-    }
+    };
+}
+
+pub mod rc;
+
+lazy_comp_impl! {
+    sync;
+    /// Don't bother with his complex declarations, just know
+    /// that it implements `Clone` and `IntoIter`.
+    ///
+    /// [`LazyComp::iter`] and `&LazyComp` iterate it many times without consuming
+    /// it. Each iteration runs a clone of its closure, so the captures must be
+    /// `Clone`, and a collection moved in is cloned every time. Borrow it instead
+    /// (`let xs = &xs;` before `lazy_comp![.., x in xs]`, or `lazy_comp![ref: ...]`)
+    /// to replay the comprehension cheaply.
+    pub struct LazyComp;
+
+    /// It does not implement `Clone`. Please use ```lazy_comp![].iter()``` to
+    /// start the comprehension over again.
+    pub struct IntoIter;
+}
+
+/// Fixes the signature of the closure of a generator kept on the stack by
+/// [`crate::let_lazy_comp`], which is built before `Gen::new` is called.
+#[doc(hidden)]
+pub fn producer<'s, Y: 's, F, Fu>(f: F) -> F
+where
+    F: FnOnce(genawaiter::stack::Co<'s, Y>) -> Fu,
+    Fu: Future<Output = ()>,
+{
+    f
 }
 
 /// Syntax is the same as [`super::comp`], except that it's lazy.
 /// # Example
 /// ```rust
-/// use list_comprehension::{lazy_comp, LazyComp};
+/// use list_comprehension::lazy_comp;
 ///
 /// // example 3
 /// let arr3 = lazy_comp![
@@ -103,200 +174,228 @@ impl<Y, Fu: Future<Output = ()>> Iterator for IntoIter<Y, Fu> {
 /// // 2
 /// // ------
 /// ```
+///
+/// With the `fair:` prefix, the generators are enumerated diagonally instead of
+/// being nested, so every binding is reached even if they are infinite. The
+/// generators can't use the variables bound before them, and their items are
+/// cloned.
+/// ```rust
+/// use list_comprehension::lazy_comp;
+///
+/// let pairs = lazy_comp![fair: (x, y), x in 0.., y in 0..];
+/// let first: Vec<(i32, i32)> = pairs.into_iter().take(6).collect();
+/// assert_eq!(first, [(0, 0), (0, 1), (1, 0), (0, 2), (1, 1), (2, 0)]);
+/// ```
+///
+/// The captures are moved into the comprehension. With the `ref:` prefix (or
+/// `ref fair:`), they are borrowed instead, like by a closure without `move`,
+/// unless the comprehension consumes them.
+/// ```rust
+/// use list_comprehension::lazy_comp;
+///
+/// let words = vec!["apple", "banana"];
+/// let lens = lazy_comp![ref: w.len(), w in &words];
+/// assert_eq!(lens.iter().collect::<Vec<_>>(), [5, 6]);
+/// assert_eq!(words.len(), 2);
+/// ```
+///
+/// With the `rc:` prefix, it returns a [`crate::lazy::rc::LazyComp`], which is
+/// cheaper but isn't `Send`. Prefixes can be combined, e.g. `ref rc fair:`.
+/// [`crate::let_lazy_comp`] keeps the generator on the stack.
 #[macro_export]
 macro_rules! lazy_comp {
-    ($out:expr => $( $unparsed:tt )+) => {
+    // 入口
+    // `@stack` comes from `let_lazy_comp`, and goes first so that `stack` isn't taken for `$capture`
+    (@stack $shelf:ident $( $prefix:ident )+ : $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@prefix [moved [stack $shelf] []] $( $prefix )+ : $( $unparsed )+)
+    };
+
+    (@stack $shelf:ident $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@moved [stack $shelf] [] $( $unparsed )+)
+    };
+
+    // 展开
+    (@moved [sync] [$( $mode:ident )?] @out ($( $out:tt )+) $( $unparsed:tt )+) => {
         {
-            LazyComp::new(|co: genawaiter::sync::Co<_>| async move {
-                $crate::lazy_parse!(co; $out; $( $unparsed )+);
-            })
+            // not passed to `new` right away, which would make it `FnOnce`
+            let func = |co: $crate::__private::genawaiter::sync::Co<_>| async move {
+                $crate::lazy_parse!($( $mode )? co; $( $out )+; $( $unparsed )+);
+            };
+            $crate::LazyComp::new(func)
         }
     };
 
-    ($out:expr , $( $unparsed:tt )+) => {
+    (@borrowed [sync] [$( $mode:ident )?] @out ($( $out:tt )+) $( $unparsed:tt )+) => {
         {
-            LazyComp::new(|co: genawaiter::sync::Co<_>| async move {
-                $crate::lazy_parse!(co; $out; $( $unparsed )+);
-            })
+            let func = |co: $crate::__private::genawaiter::sync::Co<_>| async {
+                // only `co` is moved, the captures are borrowed unless they are consumed
+                let co = co;
+                $crate::lazy_parse!($( $mode )? co; $( $out )+; $( $unparsed )+);
+            };
+            $crate::LazyComp::new(func)
         }
     };
 
-    ($out:expr ; $( $unparsed:tt )+) => {
+    (@moved [rc] [$( $mode:ident )?] @out ($( $out:tt )+) $( $unparsed:tt )+) => {
         {
-            LazyComp::new(|co: genawaiter::sync::Co<_>| async move {
-                $crate::lazy_parse!(co; $out; $( $unparsed )+);
-            })
+            let func = |co: $crate::__private::genawaiter::rc::Co<_>| async move {
+                $crate::lazy_parse!($( $mode )? co; $( $out )+; $( $unparsed )+);
+            };
+            $crate::lazy::rc::LazyComp::new(func)
         }
     };
-}
 
-#[macro_export]
-macro_rules! lazy_parse {
-    // 递归展开
-    (
-        $co:ident;
-        $out:expr;
-        $var:ident in $iter:expr
-        $(, $( $unparsed:tt )* )?
-    ) => {
-        for $var in $iter {
-            $crate::lazy_parse!(
-                $co; $out; $($( $unparsed )*)?
-            );
+    (@borrowed [rc] [$( $mode:ident )?] @out ($( $out:tt )+) $( $unparsed:tt )+) => {
+        {
+            let func = |co: $crate::__private::genawaiter::rc::Co<_>| async {
+                let co = co;
+                $crate::lazy_parse!($( $mode )? co; $( $out )+; $( $unparsed )+);
+            };
+            $crate::lazy::rc::LazyComp::new(func)
         }
     };
 
-    (
-        $co:ident;
-        $out:expr;
-        $var:ident <- $iter:expr
-        $(, $( $unparsed:tt )* )?
-    ) => {
-        for $var in $iter {
-            $crate::lazy_parse!(
-                $co; $out; $($( $unparsed )*)?
-            );
+    // the user code is built outside of `unsafe`, which only covers `Gen::new`
+    (@moved [stack $shelf:ident] [$( $mode:ident )?] @out ($( $out:tt )+) $( $unparsed:tt )+) => {
+        {
+            let producer = $crate::lazy::producer(|co| async move {
+                $crate::lazy_parse!($( $mode )? co; $( $out )+; $( $unparsed )+);
+            });
+            // Safety: `co` is hygienic, so the user code can't move it out of the generator
+            unsafe { $crate::__private::genawaiter::stack::Gen::new(&mut $shelf, producer) }
         }
     };
 
-    (
-        $co:ident;
-        $out:expr;
-        let $var:ident $(: $ty:ty)? = $expr:expr
-        $(, $( $unparsed:tt )* )?
-    ) => {
-        let $var $(: $ty)? = $expr;
+    (@borrowed [stack $shelf:ident] [$( $mode:ident )?] @out ($( $out:tt )+) $( $unparsed:tt )+) => {
+        {
+            let producer = $crate::lazy::producer(|co| async {
+                let co = co;
+                $crate::lazy_parse!($( $mode )? co; $( $out )+; $( $unparsed )+);
+            });
+            // Safety: as above
+            unsafe { $crate::__private::genawaiter::stack::Gen::new(&mut $shelf, producer) }
+        }
+    };
 
-        $crate::lazy_parse!(
-            $co; $out; $($( $unparsed )*)?
-        );
+    (@$capture:ident $backend:tt $mode:tt .. $out:expr => $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@$capture $backend $mode @out (.. $out) $( $unparsed )+)
     };
 
-    (
-        $co:ident;
-        $out:expr;
-        let mut $var:ident $(: $ty:ty)? = $expr:expr
-        $(, $( $unparsed:tt )* )?
-    ) => {
-        let mut $var $(: $ty)? = $expr;
+    (@$capture:ident $backend:tt $mode:tt .. $out:expr , $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@$capture $backend $mode @out (.. $out) $( $unparsed )+)
+    };
 
-        $crate::lazy_parse!(
-            $co; $out; $($( $unparsed )*)?
-        );
+    (@$capture:ident $backend:tt $mode:tt .. $out:expr ; $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@$capture $backend $mode @out (.. $out) $( $unparsed )+)
     };
 
-    (
-        $co:ident;
-        $out:expr;
-        let { $( $let_stmts:tt )* }
-        $(, $( $unparsed:tt )* )?
-    ) => {
-        $crate::let_parse_entrance!($( $let_stmts )*);
+    (@$capture:ident $backend:tt $mode:tt $out:expr => $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@$capture $backend $mode @out ($out) $( $unparsed )+)
+    };
 
-        $crate::lazy_parse!(
-            $co; $out; $($( $unparsed )*)?
-        );
+    (@$capture:ident $backend:tt $mode:tt $out:expr , $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@$capture $backend $mode @out ($out) $( $unparsed )+)
     };
 
-    (
-        $co:ident;
-        $out:expr;
-        let mut { $( $let_stmts:tt )* }
-        $(, $( $unparsed:tt )* )?
-    ) => {
-        $crate::let_parse_entrance!(all_mut @@ $( $let_stmts )*);
+    (@$capture:ident $backend:tt $mode:tt $out:expr ; $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@$capture $backend $mode @out ($out) $( $unparsed )+)
+    };
 
-        $crate::lazy_parse!(
-            $co; $out; $($( $unparsed )*)?
-        );
+    // 前缀: `ref`, `rc` and `fair`, in any order, before `:`
+    (@prefix [$capture:ident $backend:tt $mode:tt] ref $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@prefix [borrowed $backend $mode] $( $unparsed )+)
     };
 
-    (
-        $co:ident;
-        $out:expr;
-        let $var:pat = $expr:expr , else { $( $else_code:tt )* }
-        $(, $( $unparsed:tt )* )?
-    ) => {
-        let $var = $expr else { $( $else_code )* };
+    (@prefix [$capture:ident [sync] $mode:tt] rc $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@prefix [$capture [rc] $mode] $( $unparsed )+)
+    };
 
-        $crate::lazy_parse!(
-            $co; $out; $($( $unparsed )*)?
-        );
+    (@prefix [$capture:ident $backend:tt []] fair $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@prefix [$capture $backend [fair]] $( $unparsed )+)
     };
 
-    (
-        $co:ident;
-        $out:expr;
-        let $var:pat = $expr:expr
-        $(, $( $unparsed:tt )* )?
-    ) => {
-        let $var = $expr;
+    (@prefix [$capture:ident $backend:tt $mode:tt] : $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@$capture $backend $mode $( $unparsed )+)
+    };
 
-        $crate::lazy_parse!(
-            $co; $out; $($( $unparsed )*)?
-        );
+    // 入口
+    ($( $prefix:ident )+ : $( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@prefix [moved [sync] []] $( $prefix )+ : $( $unparsed )+)
     };
 
-    (
-        $co:ident;
-        $out:expr;
-        for $var:pat in $iter:expr
-        $(, $( $unparsed:tt )* )?
-    ) => {
-        for $var in $iter {
-            $crate::lazy_parse!(
-                $co; $out; $($( $unparsed )*)?
-            );
-        }
+    ($( $unparsed:tt )+) => {
+        $crate::lazy_comp!(@moved [sync] [] $( $unparsed )+)
+    };
+}
+
+/// Same as [`crate::lazy_comp`], except that the generator is kept on the stack
+/// instead of the heap: `let_lazy_comp!(name = [Out, Qual1, ..., Qualn])` declares
+/// `name`, a generator that can only be used in the current scope. The prefixes
+/// `ref:` and `fair:` of `lazy_comp` can be used too.
+/// # Example
+/// ```rust
+/// use list_comprehension::let_lazy_comp;
+///
+/// let_lazy_comp!(evens = [x, x in 0..10, x % 2 == 0]);
+/// assert_eq!(evens.into_iter().collect::<Vec<_>>(), [0, 2, 4, 6, 8]);
+/// ```
+///
+/// Building the generator is `unsafe`, but the comprehension itself isn't
+/// part of the `unsafe` block:
+/// ```compile_fail
+/// use list_comprehension::let_lazy_comp;
+///
+/// let x = 1;
+/// let p = &x as *const i32;
+/// // error: dereference of raw pointer is unsafe
+/// let_lazy_comp!(ys = [*p, _ in 0..1]);
+/// ```
+#[macro_export]
+macro_rules! let_lazy_comp {
+    ($name:ident = [ $( $comp:tt )+ ]) => {
+        let mut shelf = $crate::__private::genawaiter::stack::Shelf::new();
+        #[allow(unused_mut)]
+        let mut $name = $crate::lazy_comp!(@stack shelf $( $comp )+);
     };
+}
 
+/// Lowers the qualifiers with [`crate::parse`], yielding `$out` (or every
+/// item of it if it's spread) through `$co` for every surviving binding.
+#[macro_export]
+macro_rules! lazy_parse {
     (
-        $co:ident;
-        $out:expr;
-        $pred:expr
-        $(, $( $unparsed:tt )* )?
+        fair $co:ident;
+        .. $out:expr;
+        $( $unparsed:tt )*
     ) => {
-        if !($pred) {
-            continue;
-        }
-
-        $crate::lazy_parse!(
-            $co; $out; $($( $unparsed )*)?
-        );
+        $crate::__private::fair!($crate $crate::parse { @spread item in $out => {
+            $co.yield_(item).await;
+        } }; $( $unparsed )*);
     };
 
     (
-        $co:ident;
+        fair $co:ident;
         $out:expr;
-        $let_stmt:stmt
-        $(, $( $unparsed:tt )* )?
+        $( $unparsed:tt )*
     ) => {
-        $let_stmt;
-
-        $crate::lazy_parse!(
-            $co; $out; $($( $unparsed )*)?
-        );
+        $crate::__private::fair!($crate $crate::parse { $co.yield_($out).await; }; $( $unparsed )*);
     };
 
     (
         $co:ident;
-        $out:expr;
-        $var:pat in $iter:expr
-        $(, $( $unparsed:tt )* )?
+        .. $out:expr;
+        $( $unparsed:tt )*
     ) => {
-        for $var in $iter {
-            $crate::lazy_parse!(
-                $co; $out; $($( $unparsed )*)?
-            );
-        }
+        $crate::parse!({ @spread item in $out => {
+            $co.yield_(item).await;
+        } }; $( $unparsed )*);
     };
 
-
-    // 结束条件
     (
         $co:ident;
         $out:expr;
+        $( $unparsed:tt )*
     ) => {
-        $co.yield_($out).await;
+        $crate::parse!({ $co.yield_($out).await; }; $( $unparsed )*);
     };
 }
@@ -0,0 +1,19 @@
+//! The single-threaded flavour of [`crate::LazyComp`], returned by
+//! `lazy_comp![rc: ...]`. Its generator shares its state through an `Rc`
+//! instead of an `Arc`, so it's cheaper to resume, but it isn't `Send`.
+
+use core::future::Future;
+
+lazy_comp_impl! {
+    rc;
+    /// Same as [`crate::LazyComp`], with an `Rc` based generator, so neither
+    /// it nor its iterator is `Send`.
+    ///
+    /// [`LazyComp::iter`] and `&LazyComp` replay it by cloning the closure,
+    /// just like the `sync` flavour.
+    pub struct LazyComp;
+
+    /// It does not implement `Clone`, nor `Send`. Please use
+    /// ```lazy_comp![rc: ...].iter()``` to start the comprehension over again.
+    pub struct IntoIter;
+}
@@ -1,14 +1,79 @@
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
+
 #[cfg(feature = "lazy_comp")]
 pub mod lazy;
 
 #[cfg(feature = "lazy_comp")]
 pub use lazy::{IntoIter, LazyComp};
 
+#[cfg(feature = "stream_comp")]
+pub mod stream;
+
+#[cfg(feature = "stream_comp")]
+pub use stream::StreamComp;
+
+#[cfg(feature = "alloc")]
+pub mod fallible;
+
+mod iter;
+
+#[doc(hidden)]
+pub mod parallel;
+
+#[cfg(feature = "rayon")]
+mod par;
+
+pub mod map;
+
+#[doc(hidden)]
+pub mod aggregate;
+
+pub mod capacity;
+
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub mod join;
+
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+pub mod transform;
+
+#[cfg(feature = "alloc")]
+pub mod fair;
+
+pub use map::MapInsert;
+
+pub use capacity::{CapacityError, SliceBuf, TryPush};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use list_comprehension_macros::{fair, normalize};
+
+    #[cfg(feature = "alloc")]
+    pub use alloc::vec::Vec;
+
+    #[cfg(feature = "lazy_comp")]
+    pub use genawaiter;
+
+    #[cfg(feature = "rayon")]
+    pub use rayon;
+}
+
 /// # List Comprehension
 /// A macro for Haskell-like list comprehensions in Rust
 ///
+/// It collects into `Vec`, so it requires the `alloc` feature, except for
+/// the aggregate forms, which don't allocate.
+///
 /// # Examples:
-/// ```rust
+#[cfg_attr(feature = "alloc", doc = "```rust")]
+#[cfg_attr(not(feature = "alloc"), doc = "```ignore")]
 /// use list_comprehension::comp;
 ///
 /// // example 1
@@ -35,14 +100,153 @@ pub use lazy::{IntoIter, LazyComp};
 ///     , let mut b3: i8 = 1
 ///     , let Some(num) = Some(114) else { panic!("Actually this panic shouldn't be called") }
 /// ];
+///
+/// // a spread output `..Exp` adds every item of `Exp`
+/// let arr4: Vec<i32> = comp![..[n, -n] => n in 1..3];
+/// assert_eq!(arr4, [1, -1, 2, -2]);
+///
+/// // an aggregate form accumulates the outputs without building a `Vec`
+/// let dot: i32 = comp![sum: x * y, (x, y) in [(1, 2), (3, 4)]];
+/// assert_eq!(dot, 14);
+/// assert_eq!(comp![count: n in 0..10, n % 3 == 0], 4);
+/// assert_eq!(comp![max_by_key(|s: &&str| s.len()): s, s in ["a", "bcd", "ef"]], Some("bcd"));
+/// assert_eq!(comp![fold(String::new(), |acc, c| acc + c): c, c in ["a", "b"]], "ab");
+/// assert!(comp![any: n > 5, n in 0..]);
 /// ```
 /// More details can be found in README.md
 #[macro_export]
 macro_rules! comp {
+    (count: $( $unparsed:tt )+) => {
+        {
+            let mut acc: usize = 0;
+            $crate::parse!({ acc += 1; }; $( $unparsed )+);
+
+            acc
+        }
+    };
+
+    ($mode:ident ( $( $args:tt )* ): $( $unparsed:tt )+) => {
+        $crate::aggregate!($mode ($( $args )*) $( $unparsed )+)
+    };
+
+    ($mode:ident: $( $unparsed:tt )+) => {
+        $crate::aggregate!($mode () $( $unparsed )+)
+    };
+
+    (.. $out:expr => $( $unparsed:tt )+) => {
+        {
+            let mut res = $crate::__private::Vec::new();
+            $crate::parse!({ @spread item in $out => { res.push(item); } }; $( $unparsed )+);
+
+            res
+        }
+    };
+
+    (.. $out:expr , $( $unparsed:tt )+) => {
+        {
+            let mut res = $crate::__private::Vec::new();
+            $crate::parse!({ @spread item in $out => { res.push(item); } }; $( $unparsed )+);
+
+            res
+        }
+    };
+
+    (.. $out:expr ; $( $unparsed:tt )+) => {
+        {
+            let mut res = $crate::__private::Vec::new();
+            $crate::parse!({ @spread item in $out => { res.push(item); } }; $( $unparsed )+);
+
+            res
+        }
+    };
+
+    ($out:expr => $( $unparsed:tt )+) => {
+        {
+            let mut res = $crate::__private::Vec::new();
+            $crate::parse!({ res.push($out); }; $( $unparsed )+);
+
+            res
+        }
+    };
+
+    ($out:expr , $( $unparsed:tt )+) => {
+        {
+            let mut res = $crate::__private::Vec::new();
+            $crate::parse!({ res.push($out); }; $( $unparsed )+);
+
+            res
+        }
+    };
+
+    ($out:expr ; $( $unparsed:tt )+) => {
+        {
+            let mut res = $crate::__private::Vec::new();
+            $crate::parse!({ res.push($out); }; $( $unparsed )+);
+
+            res
+        }
+    };
+}
+
+/// Syntax is the same as [`comp`], except that the result is collected
+/// into any collection implementing `Default + Extend`, such as `HashSet`,
+/// `BTreeMap`, `VecDeque` or `String`. The target type is inferred, so it
+/// usually needs an annotation.
+/// # Example
+/// ```rust
+/// use list_comprehension::comp_into;
+/// use std::collections::{BTreeMap, HashSet};
+///
+/// let set: HashSet<i32> = comp_into![n % 3 => n in 0..10];
+/// assert_eq!(set, HashSet::from([0, 1, 2]));
+///
+/// let map: BTreeMap<i32, i32> = comp_into![(n, n * n), n in 0..3];
+/// assert_eq!(map, BTreeMap::from([(0, 0), (1, 1), (2, 4)]));
+///
+/// let s: String = comp_into![c.to_ascii_uppercase(); c in "abc".chars(), c != 'b'];
+/// assert_eq!(s, "AC");
+/// ```
+#[macro_export]
+macro_rules! comp_into {
+    (.. $out:expr => $( $unparsed:tt )+) => {
+        {
+            let mut res = ::core::default::Default::default();
+            $crate::parse!({ @spread item in $out => {
+                ::core::iter::Extend::extend(&mut res, ::core::iter::once(item));
+            } }; $( $unparsed )+);
+
+            res
+        }
+    };
+
+    (.. $out:expr , $( $unparsed:tt )+) => {
+        {
+            let mut res = ::core::default::Default::default();
+            $crate::parse!({ @spread item in $out => {
+                ::core::iter::Extend::extend(&mut res, ::core::iter::once(item));
+            } }; $( $unparsed )+);
+
+            res
+        }
+    };
+
+    (.. $out:expr ; $( $unparsed:tt )+) => {
+        {
+            let mut res = ::core::default::Default::default();
+            $crate::parse!({ @spread item in $out => {
+                ::core::iter::Extend::extend(&mut res, ::core::iter::once(item));
+            } }; $( $unparsed )+);
+
+            res
+        }
+    };
+
     ($out:expr => $( $unparsed:tt )+) => {
         {
-            let mut res = Vec::new();
-            $crate::parse!(res; $out; $( $unparsed )+);
+            let mut res = ::core::default::Default::default();
+            $crate::parse!({
+                ::core::iter::Extend::extend(&mut res, ::core::iter::once($out));
+            }; $( $unparsed )+);
 
             res
         }
@@ -50,8 +254,10 @@ macro_rules! comp {
 
     ($out:expr , $( $unparsed:tt )+) => {
         {
-            let mut res = Vec::new();
-            $crate::parse!(res; $out; $( $unparsed )+);
+            let mut res = ::core::default::Default::default();
+            $crate::parse!({
+                ::core::iter::Extend::extend(&mut res, ::core::iter::once($out));
+            }; $( $unparsed )+);
 
             res
         }
@@ -59,269 +265,525 @@ macro_rules! comp {
 
     ($out:expr ; $( $unparsed:tt )+) => {
         {
-            let mut res = Vec::new();
-            $crate::parse!(res; $out; $( $unparsed )+);
+            let mut res = ::core::default::Default::default();
+            $crate::parse!({
+                ::core::iter::Extend::extend(&mut res, ::core::iter::once($out));
+            }; $( $unparsed )+);
 
             res
         }
     };
 }
 
+/// Syntax is the same as [`comp`], except that the output is a block, which
+/// runs for every surviving binding and must evaluate to `()`. Nothing is
+/// collected, so it's a loop nest that also understands `let { ... }`, `take`,
+/// `zip` and the other qualifiers.
+/// # Example
+/// ```rust
+/// use list_comprehension::for_comp;
+/// use std::collections::HashMap;
+///
+/// let mut squares = HashMap::new();
+/// for_comp![
+///     { squares.insert(n, n * n); }
+///     , n in 0..10
+///     , n % 3 == 0
+/// ];
+/// assert_eq!(squares.len(), 4);
+/// assert_eq!(squares[&9], 81);
+/// ```
+#[macro_export]
+macro_rules! for_comp {
+    ($body:block => $( $unparsed:tt )+) => {
+        {
+            $crate::parse!({ $body }; $( $unparsed )+);
+        }
+    };
+
+    ($body:block , $( $unparsed:tt )+) => {
+        {
+            $crate::parse!({ $body }; $( $unparsed )+);
+        }
+    };
+
+    ($body:block ; $( $unparsed:tt )+) => {
+        {
+            $crate::parse!({ $body }; $( $unparsed )+);
+        }
+    };
+}
+
+/// Syntax is the same as [`comp`], except that it returns the first output as
+/// `Option<T>`. Nothing is allocated, and the search stops at the first
+/// surviving binding, however deep it is in the loops.
+/// # Example
+/// ```rust
+/// use list_comprehension::find_comp;
+///
+/// let triple = find_comp![
+///     (a, b, c)
+///     , c in 1..
+///     , b in 1..c
+///     , a in 1..b
+///     , a * a + b * b == c * c
+///     , a + b + c > 20
+/// ];
+/// assert_eq!(triple, Some((6, 8, 10)));
+/// ```
+#[macro_export]
+macro_rules! find_comp {
+    ($out:expr => $( $unparsed:tt )+) => {
+        {
+            let mut found = ::core::option::Option::None;
+            $crate::parse!('find {
+                found = ::core::option::Option::Some($out);
+                break 'find;
+            }; $( $unparsed )+);
+
+            found
+        }
+    };
+
+    ($out:expr , $( $unparsed:tt )+) => {
+        {
+            let mut found = ::core::option::Option::None;
+            $crate::parse!('find {
+                found = ::core::option::Option::Some($out);
+                break 'find;
+            }; $( $unparsed )+);
+
+            found
+        }
+    };
+
+    ($out:expr ; $( $unparsed:tt )+) => {
+        {
+            let mut found = ::core::option::Option::None;
+            $crate::parse!('find {
+                found = ::core::option::Option::Some($out);
+                break 'find;
+            }; $( $unparsed )+);
+
+            found
+        }
+    };
+}
+
+/// The backend shared by the comprehension macros.
+///
+/// It's invoked as `parse!({ sink }; qualifiers)`, where `sink` is the code
+/// run for every surviving binding. The qualifiers are lowered into nested
+/// loops inside a block labelled `'comp`, which `take` and `break` use to end
+/// the comprehension early. Each loop is labelled too, so that `while` ends
+/// only the innermost generator before it. With `parse!('label { sink }; qualifiers)`
+/// the block is labelled `'label` instead, so the sink can end it too.
+/// The sink `{ @spread item in Exp => { body } }` runs `body` for every `item`
+/// of `Exp` instead, so that `take` counts the items of a spread output.
+///
+/// The qualifiers are first checked by the procedural macro
+/// `list_comprehension_macros::normalize`, so a malformed one is reported at
+/// the offending token:
+/// ```compile_fail
+/// use list_comprehension::comp;
+///
+/// // error: expected generator, let or guard after `,`
+/// let arr: Vec<i32> = comp![x, x in 0..3, => 2];
+/// ```
 #[macro_export]
 macro_rules! parse {
+    // 入口
+    (
+        { $( $sink:tt )* };
+        $( $unparsed:tt )*
+    ) => {
+        $crate::parse!('comp { $( $sink )* }; $( $unparsed )*);
+    };
+
+    (
+        $stop:lifetime { $( $sink:tt )* };
+        $( $unparsed:tt )*
+    ) => {
+        let mut count: usize = 0;
+        let limit = usize::MAX;
+        $stop: {
+            $crate::__private::normalize!(
+                $crate::parse [{ $( $sink )* } count limit $stop $stop]; $( $unparsed )*
+            );
+        }
+    };
+
     // 递归展开
     (
-        $res:ident;
-        $out:expr;
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
         $var:ident in $iter:expr
         $(, $( $unparsed:tt )* )?
     ) => {
-        for $var in $iter {
+        'generator: for $var in $iter {
             $crate::parse!(
-                $res; $out; $($( $unparsed )*)?
+                [$sink $count $limit $stop 'generator]; $($( $unparsed )*)?
             );
         }
     };
 
     (
-        $res:ident;
-        $out:expr;
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
         $var:ident <- $iter:expr
         $(, $( $unparsed:tt )* )?
     ) => {
-        for $var in $iter {
+        'generator: for $var in $iter {
             $crate::parse!(
-                $res; $out; $($( $unparsed )*)?
+                [$sink $count $limit $stop 'generator]; $($( $unparsed )*)?
             );
         }
     };
 
     (
-        $res:ident;
-        $out:expr;
+        $ctx:tt;
         let $var:ident $(: $ty:ty)? = $expr:expr
         $(, $( $unparsed:tt )* )?
     ) => {
         let $var $(: $ty)? = $expr;
 
         $crate::parse!(
-            $res; $out; $($( $unparsed )*)?
+            $ctx; $($( $unparsed )*)?
         );
     };
 
     (
-        $res:ident;
-        $out:expr;
+        $ctx:tt;
         let mut $var:ident $(: $ty:ty)? = $expr:expr
         $(, $( $unparsed:tt )* )?
     ) => {
         let mut $var $(: $ty)? = $expr;
 
         $crate::parse!(
-            $res; $out; $($( $unparsed )*)?
+            $ctx; $($( $unparsed )*)?
         );
     };
 
     (
-        $res:ident;
-        $out:expr;
-        let { $( $let_stmts:tt )* }
+        $ctx:tt;
+        let $var:pat = $expr:expr , else { $( $else_code:tt )* }
         $(, $( $unparsed:tt )* )?
     ) => {
-        $crate::let_parse_entrance!($( $let_stmts )*);
+        let $var = $expr else { $( $else_code )* };
 
         $crate::parse!(
-            $res; $out; $($( $unparsed )*)?
+            $ctx; $($( $unparsed )*)?
         );
     };
 
     (
-        $res:ident;
-        $out:expr;
-        let mut { $( $let_stmts:tt )* }
+        $ctx:tt;
+        let $var:pat = $expr:expr
         $(, $( $unparsed:tt )* )?
     ) => {
-        $crate::let_parse_entrance!(all_mut @@ $( $let_stmts )*);
+        let $var = $expr;
 
         $crate::parse!(
-            $res; $out; $($( $unparsed )*)?
+            $ctx; $($( $unparsed )*)?
         );
     };
 
     (
-        $res:ident;
-        $out:expr;
-        let $var:pat = $expr:expr , else { $( $else_code:tt )* }
+        $ctx:tt;
+        zip $( { $( $branch:tt )* } )+
         $(, $( $unparsed:tt )* )?
     ) => {
-        let $var = $expr else { $( $else_code )* };
+        $crate::parallel!(
+            (parse [$ctx] zip) [] []; $( { $( $branch )* } )+ ; $($( $unparsed )*)?
+        );
+    };
 
-        $crate::parse!(
-            $res; $out; $($( $unparsed )*)?
+    (
+        $ctx:tt;
+        zip_eq $( { $( $branch:tt )* } )+
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        $crate::parallel!(
+            (parse [$ctx] zip_eq) [] []; $( { $( $branch )* } )+ ; $($( $unparsed )*)?
         );
     };
 
     (
-        $res:ident;
-        $out:expr;
-        let $var:pat = $expr:expr
+        $ctx:tt;
+        join_index ($index:ident, $var:pat in $iter:expr) ($key:expr)
         $(, $( $unparsed:tt )* )?
     ) => {
-        let $var = $expr;
+        let $index = $crate::join::index($iter, |item| match item {
+            $var => ::core::option::Option::Some($key),
+            #[allow(unreachable_patterns)]
+            _ => ::core::option::Option::None,
+        });
 
         $crate::parse!(
-            $res; $out; $($( $unparsed )*)?
+            $ctx; $($( $unparsed )*)?
         );
     };
 
     (
-        $res:ident;
-        $out:expr;
-        for $var:pat in $iter:expr
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
+        join ($index:ident, $var:pat) ($key:expr)
         $(, $( $unparsed:tt )* )?
     ) => {
-        for $var in $iter {
+        'generator: for item in $crate::join::probe(&$index, &$key) {
+            #[allow(irrefutable_let_patterns)]
+            let $var = item else { continue };
+
             $crate::parse!(
-                $res; $out; $($( $unparsed )*)?
+                [$sink $count $limit $stop 'generator]; $($( $unparsed )*)?
             );
         }
     };
 
+    // 变换
+    // the qualifiers before `sort by`, `group by` and `drop` are expanded on their own,
+    // with their own `take` count and `break` label
     (
-        $res:ident;
-        $out:expr;
-        $pred:expr
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
+        sort ($( $var:ident ),*) ($key:expr) { $( $parsed:tt )* }
         $(, $( $unparsed:tt )* )?
     ) => {
-        if !($pred) {
-            continue;
+        let mut bindings = $crate::__private::Vec::new();
+        {
+            let mut count: usize = 0;
+            let limit = usize::MAX;
+            'transform: {
+                $crate::parse!([{ bindings.push(($( $var, )*)); } count limit 'transform 'transform]; $( $parsed )*);
+            }
         }
 
-        $crate::parse!(
-            $res; $out; $($( $unparsed )*)?
-        );
-    };
+        bindings.sort_by(|lhs, rhs| {
+            #[allow(unused_variables)]
+            let lhs = { let ($( $var, )*) = lhs; $key };
+            #[allow(unused_variables)]
+            let rhs = { let ($( $var, )*) = rhs; $key };
+            ::core::cmp::Ord::cmp(&lhs, &rhs)
+        });
 
-    (
-        $res:ident;
-        $out:expr;
-        $let_stmt:stmt
-        $(, $( $unparsed:tt )* )?
-    ) => {
-        $let_stmt;
+        'generator: for item in bindings {
+            #[allow(unused_variables)]
+            let ($( $var, )*) = item;
 
-        $crate::parse!(
-            $res; $out; $($( $unparsed )*)?
-        );
+            $crate::parse!(
+                [$sink $count $limit $stop 'generator]; $($( $unparsed )*)?
+            );
+        }
     };
 
     (
-        $res:ident;
-        $out:expr;
-        $var:pat in $iter:expr
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
+        group ($( $var:ident ),*) ($key:expr) { $( $parsed:tt )* }
         $(, $( $unparsed:tt )* )?
     ) => {
-        for $var in $iter {
+        let mut bindings = $crate::__private::Vec::new();
+        {
+            let mut count: usize = 0;
+            let limit = usize::MAX;
+            'transform: {
+                $crate::parse!([{ bindings.push(($( $var, )*)); } count limit 'transform 'transform]; $( $parsed )*);
+            }
+        }
+
+        bindings.sort_by(|lhs, rhs| {
+            #[allow(unused_variables)]
+            let lhs = { let ($( $var, )*) = lhs; $key };
+            #[allow(unused_variables)]
+            let rhs = { let ($( $var, )*) = rhs; $key };
+            ::core::cmp::Ord::cmp(&lhs, &rhs)
+        });
+
+        let groups = $crate::transform::group_by(bindings, |lhs, rhs| {
+            #[allow(unused_variables)]
+            let lhs = { let ($( $var, )*) = lhs; $key };
+            #[allow(unused_variables)]
+            let rhs = { let ($( $var, )*) = rhs; $key };
+            lhs == rhs
+        });
+
+        'generator: for group in groups {
+            #[allow(unused_variables)]
+            let ($( $var, )*) = $crate::transform::unzip(group);
+
             $crate::parse!(
-                $res; $out; $($( $unparsed )*)?
+                [$sink $count $limit $stop 'generator]; $($( $unparsed )*)?
             );
         }
     };
 
-
-    // 结束条件
     (
-        $res:ident;
-        $out:expr;
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
+        drop ($n:expr) { $( $parsed:tt )* }
+        $(, $( $unparsed:tt )* )?
     ) => {
-        $res.push($out);
+        let skip: usize = $n;
+        let mut dropped: usize = 0;
+        {
+            let mut count: usize = 0;
+            let limit = usize::MAX;
+            'transform: {
+                $crate::parse!([{
+                    if dropped < skip {
+                        dropped += 1;
+                    } else {
+                        $crate::parse!([$sink $count $limit $stop 'transform]; $($( $unparsed )*)?);
+                    }
+                } count limit 'transform 'transform]; $( $parsed )*);
+            }
+        }
     };
-}
 
-#[macro_export]
-macro_rules! let_parse_entrance {
-    (all_mut @@ $( $let_stmts:tt )*) => {
-        $crate::let_parse_all_mut!($($let_stmts )*);
-    };
+    (
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
+        take $n:expr
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        let limit = ::core::cmp::min($limit, $n);
+        if $count >= limit {
+            break $stop;
+        }
 
-    ($( $let_stmts:tt )*) => {
-        $crate::let_parse!($($let_stmts )*);
+        $crate::parse!(
+            [$sink $count limit $stop $level]; $($( $unparsed )*)?
+        );
     };
-}
 
-#[macro_export]
-macro_rules! let_parse {
     (
-        mut $var:ident $(: $ty:ty)? = $expr:expr
-        $(; $( $let_stmts:tt )+ )?
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
+        while $cond:expr
+        $(, $( $unparsed:tt )* )?
     ) => {
-        let mut $var $(: $ty)? = $expr;
-        $crate::let_parse!($( $( $let_stmts )+ )?);
+        if !($cond) {
+            break $level;
+        }
+
+        $crate::parse!(
+            [$sink $count $limit $stop $level]; $($( $unparsed )*)?
+        );
     };
 
     (
-        $var:ident $(: $ty:ty)? = $expr:expr
-        $(; $( $let_stmts:tt )+ )?
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
+        break if $cond:expr
+        $(, $( $unparsed:tt )* )?
     ) => {
-        let $var $(: $ty)? = $expr;
-        $crate::let_parse!($( $( $let_stmts )+ )?);
+        if $cond {
+            break $stop;
+        }
+
+        $crate::parse!(
+            [$sink $count $limit $stop $level]; $($( $unparsed )*)?
+        );
     };
 
     (
-        $var:pat = $expr:expr , else { $( $else_code:tt )* }
-        $(; $( $let_stmts:tt )+ )?
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
+        break
+        $(, $( $unparsed:tt )* )?
     ) => {
-        let $var = $expr else { $( $else_code )* };
-        $crate::let_parse!($( $( $let_stmts )+ )?);
+        break $stop;
     };
 
     (
-        $var:pat = $expr:expr
-        $(; $( $let_stmts:tt )+ )?
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
+        for await $var:pat in $stream:expr
+        $(, $( $unparsed:tt )* )?
     ) => {
-        let $var = $expr;
-        $crate::let_parse!($( $( $let_stmts )+ )?);
-    };
+        let mut stream = ::core::pin::pin!($stream);
+        'generator: while let ::core::option::Option::Some(item) = $crate::stream::next(stream.as_mut()).await {
+            #[allow(irrefutable_let_patterns)]
+            let $var = item else { continue };
 
-    // 结束条件
-    () => {};
-}
+            $crate::parse!(
+                [$sink $count $limit $stop 'generator]; $($( $unparsed )*)?
+            );
+        }
+    };
 
-#[macro_export]
-macro_rules! let_parse_all_mut {
     (
-        mut $var:ident $(: $ty:ty)? = $expr:expr
-        $(; $( $let_stmts:tt )+ )?
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
+        for $var:pat in $iter:expr
+        $(, $( $unparsed:tt )* )?
     ) => {
-        let mut $var $(: $ty)? = $expr;
-        $crate::let_parse_all_mut!($( $( $let_stmts )+ )?);
+        'generator: for item in $iter {
+            #[allow(irrefutable_let_patterns)]
+            let $var = item else { continue };
+
+            $crate::parse!(
+                [$sink $count $limit $stop 'generator]; $($( $unparsed )*)?
+            );
+        }
     };
 
     (
-        $var:ident $(: $ty:ty)? = $expr:expr
-        $(; $( $let_stmts:tt )+ )?
+        $ctx:tt;
+        $pred:expr
+        $(, $( $unparsed:tt )* )?
     ) => {
-        let mut $var $(: $ty)? = $expr;
-        $crate::let_parse_all_mut!($( $( $let_stmts )+ )?);
+        if $pred {
+            $crate::parse!(
+                $ctx; $($( $unparsed )*)?
+            );
+        }
     };
 
     (
-        $var:pat = $expr:expr , else { $( $else_code:tt )* }
-        $(; $( $let_stmts:tt )+ )?
+        $ctx:tt;
+        $let_stmt:stmt
+        $(, $( $unparsed:tt )* )?
     ) => {
-        let $var = $expr else { $( $else_code )* } ;
-        $crate::let_parse_all_mut!($( $( $let_stmts )+ )?);
+        $let_stmt;
+
+        $crate::parse!(
+            $ctx; $($( $unparsed )*)?
+        );
     };
 
     (
-        $var:pat = $expr:expr
-        $(; $( $let_stmts:tt )+ )?
+        [$sink:tt $count:ident $limit:ident $stop:lifetime $level:lifetime];
+        $var:pat in $iter:expr
+        $(, $( $unparsed:tt )* )?
     ) => {
-        let $var = $expr;
-        $crate::let_parse_all_mut!($( $( $let_stmts )+ )?);
+        'generator: for item in $iter {
+            #[allow(irrefutable_let_patterns)]
+            let $var = item else { continue };
+
+            $crate::parse!(
+                [$sink $count $limit $stop 'generator]; $($( $unparsed )*)?
+            );
+        }
     };
 
 
     // 结束条件
-    () => {};
+    // a spread sink runs its body for every item of `$out`, so that `take` counts the items
+    (
+        [{ @spread $item:ident in $out:expr => { $( $sink:tt )* } } $count:ident $limit:ident $stop:lifetime $level:lifetime];
+    ) => {
+        for $item in $out {
+            $( $sink )*
+            #[allow(unreachable_code)]
+            {
+                $count += 1;
+                if $count >= $limit {
+                    break $stop;
+                }
+            }
+        }
+    };
+
+    // the sink may end with `break` or `return`, as in `find_comp`
+    (
+        [{ $( $sink:tt )* } $count:ident $limit:ident $stop:lifetime $level:lifetime];
+    ) => {
+        $( $sink )*
+        #[allow(unreachable_code)]
+        {
+            $count += 1;
+            if $count >= $limit {
+                break $stop;
+            }
+        }
+    };
 }
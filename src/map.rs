@@ -0,0 +1,158 @@
+#[cfg(feature = "alloc")]
+use alloc::collections::btree_map::{self, BTreeMap};
+#[cfg(feature = "std")]
+use core::hash::{BuildHasher, Hash};
+#[cfg(feature = "std")]
+use std::collections::hash_map::{self, HashMap};
+
+/// Insertion with a duplicate key policy, used by [`crate::map_comp`].
+///
+/// It is implemented for `HashMap` (with the `std` feature) and `BTreeMap`
+/// (with the `alloc` feature), implement it for your own map type if you want
+/// to collect into it.
+pub trait MapInsert<K, V> {
+    /// Inserts `value` only if `key` is not present yet.
+    fn insert_keep_first(&mut self, key: K, value: V);
+
+    /// Inserts `value`, replacing the old one if `key` is present.
+    fn insert_keep_last(&mut self, key: K, value: V);
+
+    /// Inserts `value`, or replaces the old value with `merge(old, value)`
+    /// if `key` is present.
+    fn insert_merge<F: FnOnce(V, V) -> V>(&mut self, key: K, value: V, merge: F);
+}
+
+#[cfg(feature = "std")]
+impl<K: Eq + Hash, V, S: BuildHasher> MapInsert<K, V> for HashMap<K, V, S> {
+    fn insert_keep_first(&mut self, key: K, value: V) {
+        self.entry(key).or_insert(value);
+    }
+
+    fn insert_keep_last(&mut self, key: K, value: V) {
+        self.insert(key, value);
+    }
+
+    fn insert_merge<F: FnOnce(V, V) -> V>(&mut self, key: K, value: V, merge: F) {
+        match self.entry(key) {
+            hash_map::Entry::Occupied(entry) => {
+                let (key, old) = entry.remove_entry();
+                self.insert(key, merge(old, value));
+            }
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<K: Ord, V> MapInsert<K, V> for BTreeMap<K, V> {
+    fn insert_keep_first(&mut self, key: K, value: V) {
+        self.entry(key).or_insert(value);
+    }
+
+    fn insert_keep_last(&mut self, key: K, value: V) {
+        self.insert(key, value);
+    }
+
+    fn insert_merge<F: FnOnce(V, V) -> V>(&mut self, key: K, value: V, merge: F) {
+        match self.entry(key) {
+            btree_map::Entry::Occupied(entry) => {
+                let (key, old) = entry.remove_entry();
+                self.insert(key, merge(old, value));
+            }
+            btree_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+        }
+    }
+}
+
+/// Dictionary comprehension. The output is `Key => Value`, the qualifiers are
+/// the same as [`crate::comp`].
+///
+/// The result is collected into any type implementing [`MapInsert`]
+/// (`HashMap`, `BTreeMap`), so it usually needs a type annotation.
+/// Duplicate keys are handled by an optional policy prefix:
+/// * `keep_last:` (default) the last value wins
+/// * `keep_first:` the first value wins
+/// * `merge(f):` the values are combined with `f(old, new)`
+/// # Example
+#[cfg_attr(feature = "std", doc = "```rust")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use list_comprehension::map_comp;
+/// use std::collections::{BTreeMap, HashMap};
+///
+/// let squares: HashMap<i32, i32> = map_comp![n => n * n, n in 0..3];
+/// assert_eq!(squares, HashMap::from([(0, 0), (1, 1), (2, 4)]));
+///
+/// let words = ["apple", "avocado", "banana"];
+/// let first: BTreeMap<char, &str> = map_comp![
+///     keep_first: w.chars().next().unwrap() => w
+///     , w in words
+/// ];
+/// assert_eq!(first, BTreeMap::from([('a', "apple"), ('b', "banana")]));
+///
+/// let count: BTreeMap<char, i32> = map_comp![
+///     merge(|old, new| old + new): w.chars().next().unwrap() => 1
+///     , w in words
+/// ];
+/// assert_eq!(count, BTreeMap::from([('a', 2), ('b', 1)]));
+/// ```
+#[macro_export]
+macro_rules! map_comp {
+    (@insert $method:ident; $key:expr; $value:expr; $( $unparsed:tt )+) => {
+        {
+            let mut res = ::core::default::Default::default();
+            $crate::parse!({
+                $crate::MapInsert::$method(&mut res, $key, $value);
+            }; $( $unparsed )+);
+
+            res
+        }
+    };
+
+    (@merge $merge:expr; $key:expr; $value:expr; $( $unparsed:tt )+) => {
+        {
+            let mut merge = $merge;
+            let mut res = ::core::default::Default::default();
+            $crate::parse!({
+                $crate::MapInsert::insert_merge(&mut res, $key, $value, &mut merge);
+            }; $( $unparsed )+);
+
+            res
+        }
+    };
+
+    (keep_first : $key:expr => $value:expr , $( $unparsed:tt )+) => {
+        $crate::map_comp!(@insert insert_keep_first; $key; $value; $( $unparsed )+)
+    };
+
+    (keep_first : $key:expr => $value:expr ; $( $unparsed:tt )+) => {
+        $crate::map_comp!(@insert insert_keep_first; $key; $value; $( $unparsed )+)
+    };
+
+    (keep_last : $key:expr => $value:expr , $( $unparsed:tt )+) => {
+        $crate::map_comp!(@insert insert_keep_last; $key; $value; $( $unparsed )+)
+    };
+
+    (keep_last : $key:expr => $value:expr ; $( $unparsed:tt )+) => {
+        $crate::map_comp!(@insert insert_keep_last; $key; $value; $( $unparsed )+)
+    };
+
+    (merge ( $merge:expr ) : $key:expr => $value:expr , $( $unparsed:tt )+) => {
+        $crate::map_comp!(@merge $merge; $key; $value; $( $unparsed )+)
+    };
+
+    (merge ( $merge:expr ) : $key:expr => $value:expr ; $( $unparsed:tt )+) => {
+        $crate::map_comp!(@merge $merge; $key; $value; $( $unparsed )+)
+    };
+
+    ($key:expr => $value:expr , $( $unparsed:tt )+) => {
+        $crate::map_comp!(@insert insert_keep_last; $key; $value; $( $unparsed )+)
+    };
+
+    ($key:expr => $value:expr ; $( $unparsed:tt )+) => {
+        $crate::map_comp!(@insert insert_keep_last; $key; $value; $( $unparsed )+)
+    };
+}
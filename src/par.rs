@@ -0,0 +1,86 @@
+/// Syntax is the same as [`crate::comp`], except that the comprehension runs
+/// in parallel on the `rayon` thread pool. The first qualifier has to be a
+/// generator whose expression implements `IntoParallelIterator`, each of its
+/// elements is handled by a separate task. Only this outermost generator is
+/// parallelised, the qualifiers after it run sequentially within the task.
+/// The result is in the same order as the one of `comp!`.
+/// ```compile_fail
+/// use list_comprehension::par_comp;
+///
+/// // error: `par_comp` needs a generator as its first qualifier
+/// let arr = par_comp![x, let n = 3, x in 0..n];
+/// ```
+///
+/// `take`, `while` and `break` aren't supported, since the elements are
+/// handled in no particular order, and neither is `join`, whose index would be
+/// built again for every element:
+/// ```compile_fail
+/// use list_comprehension::par_comp;
+///
+/// // error: `take` is not supported by `par_comp`
+/// let arr = par_comp![x, x in 0..10, take 3];
+/// ```
+/// # Example
+/// ```rust
+/// use list_comprehension::{comp, par_comp};
+///
+/// let triples = par_comp![
+///     (a, b, c)
+///     , c in 1..50u32
+///     , b in 1..c
+///     , a in 1..b
+///     , a * a + b * b == c * c
+/// ];
+/// assert_eq!(triples[0], (3, 4, 5));
+/// assert_eq!(triples, comp![(a, b, c), c in 1..50u32, b in 1..c, a in 1..b, a * a + b * b == c * c]);
+/// ```
+#[macro_export]
+macro_rules! par_comp {
+    (
+        [@par $out:expr];
+        $var:ident in $iter:expr
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        $crate::par_comp!([@par $out]; for $var in $iter $(, $( $unparsed )* )?)
+    };
+
+    (
+        [@par $out:expr];
+        for $var:pat in $iter:expr
+        $(, $( $unparsed:tt )* )?
+    ) => {
+        $crate::__private::rayon::iter::ParallelIterator::collect::<$crate::__private::Vec<_>>(
+            $crate::__private::rayon::iter::ParallelIterator::flat_map_iter(
+                $crate::__private::rayon::iter::IntoParallelIterator::into_par_iter($iter),
+                |item| {
+                    let mut res = $crate::__private::Vec::new();
+                    match item {
+                        $var => {
+                            $crate::parse!({ res.push($out); }; $($( $unparsed )*)?);
+                        }
+                        #[allow(unreachable_patterns)]
+                        _ => {}
+                    }
+
+                    res
+                },
+            ),
+        )
+    };
+
+    ([@par $out:expr]; $( $unparsed:tt )*) => {
+        ::core::compile_error!("`par_comp` needs a generator as its first qualifier")
+    };
+
+    ($out:expr => $( $unparsed:tt )+) => {
+        $crate::__private::normalize!($crate::par_comp [@par $out]; $( $unparsed )+)
+    };
+
+    ($out:expr , $( $unparsed:tt )+) => {
+        $crate::__private::normalize!($crate::par_comp [@par $out]; $( $unparsed )+)
+    };
+
+    ($out:expr ; $( $unparsed:tt )+) => {
+        $crate::__private::normalize!($crate::par_comp [@par $out]; $( $unparsed )+)
+    };
+}
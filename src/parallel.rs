@@ -0,0 +1,83 @@
+use core::iter::Zip;
+
+/// Zips two branches of a parallel comprehension, stopping at the shortest one.
+pub fn zip<A: IntoIterator, B: IntoIterator>(a: A, b: B) -> Zip<A::IntoIter, B::IntoIter> {
+    a.into_iter().zip(b)
+}
+
+/// Zips two branches of a parallel comprehension, panicking if their lengths differ.
+pub fn zip_eq<A: IntoIterator, B: IntoIterator>(a: A, b: B) -> ZipEq<A::IntoIter, B::IntoIter> {
+    ZipEq {
+        a: a.into_iter(),
+        b: b.into_iter(),
+    }
+}
+
+/// An iterator like [`Zip`], except that it panics if one of the
+/// iterators ends before the other. Created by [`zip_eq`].
+#[derive(Clone, Debug)]
+pub struct ZipEq<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A: Iterator, B: Iterator> Iterator for ZipEq<A, B> {
+    type Item = (A::Item, B::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match (self.a.next(), self.b.next()) {
+            (Some(a), Some(b)) => Some((a, b)),
+            (None, None) => None,
+            _ => panic!("branches of a `zip_eq` qualifier have different lengths"),
+        }
+    }
+}
+
+/// Lowers `zip { ... } { ... }` / `zip_eq { ... } { ... }` into a single
+/// generator over the zipped branches, then hands it back to the backend.
+///
+/// Each branch is a comprehension on its own ([`crate::iter_comp`]), whose
+/// first qualifier is a generator binding an `Ident` or a tuple of `Ident`s.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! parallel {
+    // 递归展开
+    ($args:tt [$( $pat:tt )*] [$( $iter:tt )*]; { $var:ident in $( $branch:tt )* } $( $rest:tt )*) => {
+        $crate::parallel!(@push $args [$( $pat )*] [$( $iter )*] [$var] { $var in $( $branch )* } $( $rest )*)
+    };
+
+    ($args:tt [$( $pat:tt )*] [$( $iter:tt )*]; { $var:ident <- $( $branch:tt )* } $( $rest:tt )*) => {
+        $crate::parallel!(@push $args [$( $pat )*] [$( $iter )*] [$var] { $var <- $( $branch )* } $( $rest )*)
+    };
+
+    ($args:tt [$( $pat:tt )*] [$( $iter:tt )*]; { for $var:ident in $( $branch:tt )* } $( $rest:tt )*) => {
+        $crate::parallel!(@push $args [$( $pat )*] [$( $iter )*] [$var] { $var in $( $branch )* } $( $rest )*)
+    };
+
+    ($args:tt [$( $pat:tt )*] [$( $iter:tt )*]; { ( $( $var:ident ),+ $(,)? ) in $( $branch:tt )* } $( $rest:tt )*) => {
+        $crate::parallel!(
+            @push $args [$( $pat )*] [$( $iter )*] [( $( $var ),+ )] { for ( $( $var ),+ ) in $( $branch )* } $( $rest )*
+        )
+    };
+
+    (@push $args:tt [] [] [$( $p:tt )*] { $( $branch:tt )* } $( $rest:tt )*) => {
+        $crate::parallel!($args [$( $p )*] [$crate::iter_comp![$( $p )*, $( $branch )*]]; $( $rest )*)
+    };
+
+    (
+        @push ($backend:ident [$( $head:tt )*] $zip:ident)
+        [$( $pat:tt )+] [$( $iter:tt )+] [$( $p:tt )*] { $( $branch:tt )* } $( $rest:tt )*
+    ) => {
+        $crate::parallel!(
+            ($backend [$( $head )*] $zip)
+            [($( $pat )+, $( $p )*)]
+            [$crate::parallel::$zip($( $iter )+, $crate::iter_comp![$( $p )*, $( $branch )*])];
+            $( $rest )*
+        )
+    };
+
+    // 结束条件
+    (($backend:ident [$( $head:tt )*] $zip:ident) [$( $pat:tt )+] [$( $iter:tt )+]; ; $( $unparsed:tt )*) => {
+        $crate::$backend!($( $head )*; for $( $pat )+ in $( $iter )+ , $( $unparsed )*)
+    };
+}
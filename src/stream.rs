@@ -0,0 +1,80 @@
+use futures_core::Stream;
+use genawaiter::sync::{Co, Gen};
+
+use core::future::{poll_fn, Future};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// The `Stream` returned by [`crate::stream_comp`]. Unlike [`crate::LazyComp`],
+/// the generator is built right away, since a `Stream` is polled in place.
+pub struct StreamComp<Y, Fu: Future<Output = ()>> {
+    generator: Gen<Y, (), Fu>,
+}
+
+impl<Y, Fu: Future<Output = ()>> StreamComp<Y, Fu> {
+    pub fn new<F>(f: F) -> StreamComp<Y, Fu>
+    where
+        F: FnOnce(Co<Y>) -> Fu,
+    {
+        StreamComp {
+            generator: Gen::new(f),
+        }
+    }
+}
+
+impl<Y, Fu: Future<Output = ()>> Stream for StreamComp<Y, Fu> {
+    type Item = Y;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().generator).poll_next(cx)
+    }
+}
+
+/// Waits for the next item of a stream generator (`x in stream.await_each`).
+#[doc(hidden)]
+pub async fn next<S: Stream + ?Sized>(mut stream: Pin<&mut S>) -> Option<S::Item> {
+    poll_fn(|cx| stream.as_mut().poll_next(cx)).await
+}
+
+/// Syntax is the same as [`crate::lazy_comp`], except that it returns a `Stream`
+/// and `.await` can be used in the output, `let`s and guards. A generator whose
+/// expression ends with `.await_each` iterates over a `Stream` instead of an
+/// `IntoIterator`.
+/// # Example
+/// ```rust
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use list_comprehension::stream_comp;
+///
+/// async fn square(n: i32) -> i32 {
+///     n * n
+/// }
+///
+/// let squares = stream_comp![
+///     (x, s)
+///     , x in stream::iter(0..4).await_each
+///     , let s = square(x).await
+///     , s != 4
+/// ];
+///
+/// assert_eq!(block_on(squares.collect::<Vec<_>>()), [(0, 0), (1, 1), (3, 9)]);
+/// ```
+#[macro_export]
+macro_rules! stream_comp {
+    ($out:expr => $( $unparsed:tt )+) => {
+        $crate::StreamComp::new(|co: $crate::__private::genawaiter::sync::Co<_>| async move {
+            $crate::lazy_parse!(co; $out; $( $unparsed )+);
+        })
+    };
+
+    ($out:expr , $( $unparsed:tt )+) => {
+        $crate::StreamComp::new(|co: $crate::__private::genawaiter::sync::Co<_>| async move {
+            $crate::lazy_parse!(co; $out; $( $unparsed )+);
+        })
+    };
+
+    ($out:expr ; $( $unparsed:tt )+) => {
+        $crate::StreamComp::new(|co: $crate::__private::genawaiter::sync::Co<_>| async move {
+            $crate::lazy_parse!(co; $out; $( $unparsed )+);
+        })
+    };
+}
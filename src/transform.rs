@@ -0,0 +1,65 @@
+//! Helpers of the transform qualifiers `sort by`, `group by` and `drop`, which
+//! work on the bindings of all the qualifiers before them.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Splits the sorted bindings into runs of bindings with the same key.
+pub fn group_by<T, F>(bindings: Vec<T>, mut same: F) -> Vec<Vec<T>>
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let mut groups: Vec<Vec<T>> = Vec::new();
+    for item in bindings {
+        match groups.last_mut() {
+            Some(group) if same(&group[0], &item) => group.push(item),
+            _ => groups.push(vec![item]),
+        }
+    }
+
+    groups
+}
+
+/// Turns a group of bindings `Vec<(A, B, ...)>` into `(Vec<A>, Vec<B>, ...)`.
+pub fn unzip<T: Unzip>(group: T) -> T::Output {
+    group.unzip()
+}
+
+pub trait Unzip {
+    type Output;
+
+    fn unzip(self) -> Self::Output;
+}
+
+macro_rules! impl_unzip {
+    ($( $t:ident $var:ident $idx:tt ),*) => {
+        impl<$( $t ),*> Unzip for Vec<($( $t, )*)> {
+            type Output = ($( Vec<$t>, )*);
+
+            #[allow(clippy::unused_unit)]
+            fn unzip(self) -> Self::Output {
+                $( let mut $var = Vec::with_capacity(self.len()); )*
+                #[allow(unused_variables)]
+                for item in self {
+                    $( $var.push(item.$idx); )*
+                }
+
+                ($( $var, )*)
+            }
+        }
+    };
+}
+
+impl_unzip!();
+impl_unzip!(A a 0);
+impl_unzip!(A a 0, B b 1);
+impl_unzip!(A a 0, B b 1, C c 2);
+impl_unzip!(A a 0, B b 1, C c 2, D d 3);
+impl_unzip!(A a 0, B b 1, C c 2, D d 3, E e 4);
+impl_unzip!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5);
+impl_unzip!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6);
+impl_unzip!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7);
+impl_unzip!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8);
+impl_unzip!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9);
+impl_unzip!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10);
+impl_unzip!(A a 0, B b 1, C c 2, D d 3, E e 4, F f 5, G g 6, H h 7, I i 8, J j 9, K k 10, L l 11);
@@ -8,6 +8,7 @@ struct TestS {
     age: i32,
 }
 
+#[cfg(feature = "alloc")]
 #[test]
 fn test_comp() {
     let shared_arr = [0, 1];
@@ -148,6 +149,7 @@ fn test_comp() {
     ];
 }
 
+#[cfg(feature = "lazy_comp")]
 #[test]
 fn test_lazy_comp() {
     let shared_arr = [0, 1];
@@ -311,3 +313,1153 @@ fn test_lazy_comp() {
     .into_iter()
     .collect::<Vec<_>>();
 }
+
+#[test]
+fn test_comp_into() {
+    use std::collections::{BTreeMap, HashSet, VecDeque};
+
+    let set: HashSet<i32> = comp_into![
+        n % 3
+        , n in 0..10
+    ];
+    assert_eq!(set, HashSet::from([0, 1, 2]));
+
+    let map: BTreeMap<&str, i32> = comp_into![
+        (name, age)
+        , TestS { name, age } in [
+            TestS { name: "LiHua", age: 114 },
+            TestS { name: "Jack", age: 514 },
+        ]
+    ];
+    assert_eq!(map, BTreeMap::from([("Jack", 514), ("LiHua", 114)]));
+
+    let deque: VecDeque<(i32, i32)> = comp_into![
+        (a1, a2)
+        , a1 <- [0, 1]
+        , a2 <- [2, 3]
+        , a1 != 1
+    ];
+    assert_eq!(deque, [(0, 2), (0, 3)]);
+
+    let string: String = comp_into![
+        c.to_ascii_uppercase()
+        ; c in "hello".chars()
+        , c != 'l'
+    ];
+    assert_eq!(string, "HEO");
+
+    let vec: Vec<i32> = comp_into![n => n in 0..3];
+    #[cfg(feature = "alloc")]
+    assert_eq!(vec, comp![n => n in 0..3]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_map_comp() {
+    use std::collections::{BTreeMap, HashMap};
+
+    let people = [
+        TestS {
+            name: "LiHua",
+            age: 114,
+        },
+        TestS {
+            name: "Jack",
+            age: 514,
+        },
+        TestS {
+            name: "LiHua",
+            age: 1919,
+        },
+    ];
+
+    let keep_last: HashMap<&str, i32> = map_comp![
+        name => age
+        , TestS { name, age } in people.clone()
+    ];
+    assert_eq!(keep_last, HashMap::from([("LiHua", 1919), ("Jack", 514)]));
+
+    let explicit_keep_last: HashMap<&str, i32> = map_comp![
+        keep_last: name => age
+        ; TestS { name, age } in people.clone()
+    ];
+    assert_eq!(explicit_keep_last, keep_last);
+
+    let keep_first: BTreeMap<&str, i32> = map_comp![
+        keep_first: name => age
+        , TestS { name, age } in people.clone()
+    ];
+    assert_eq!(keep_first, BTreeMap::from([("Jack", 514), ("LiHua", 114)]));
+
+    let merged: BTreeMap<&str, i32> = map_comp![
+        merge(|old, new| old + new): name => age
+        , TestS { name, age } in people.clone()
+        , age > 200
+    ];
+    assert_eq!(merged, BTreeMap::from([("Jack", 514), ("LiHua", 1919)]));
+
+    let histogram: HashMap<i32, Vec<i32>> = map_comp![
+        merge(|mut old: Vec<_>, new| { old.extend(new); old }): n % 3 => vec![n]
+        , n in 0..7
+        , let { m = n * 2 }
+        , m != 4
+    ];
+    assert_eq!(
+        histogram,
+        HashMap::from([(0, vec![0, 3, 6]), (1, vec![1, 4]), (2, vec![5])])
+    );
+}
+
+fn evens_below(limit: u32) -> impl Iterator<Item = u32> + Clone {
+    iter_comp![n, n in 0..limit, n % 2 == 0]
+}
+
+#[test]
+fn test_iter_comp() {
+    let shared_arr = [0, 1];
+
+    let first_generator_syntax = iter_comp![
+        (a1, a2)
+        , a1 <- shared_arr
+        , a2 <- [2, 3]
+    ]
+    .collect::<Vec<_>>();
+    assert_eq!(first_generator_syntax, [(0, 2), (0, 3), (1, 2), (1, 3)]);
+
+    let second_generator_syntax_with_pat = iter_comp![
+        (name, age)
+        , let t = TestS { name: "LiHua", age: 114 }
+        , TestS { name , ..} in [t.clone()]
+        , TestS { age , ..}  in [t.clone()]
+    ]
+    .collect::<Vec<_>>();
+    assert_eq!(second_generator_syntax_with_pat, [("LiHua", 114)]);
+
+    let decl_with_mut_pattern_matching = iter_comp![
+        name
+        , let TestS { mut name, .. } = TestS { name: "LiHua", age: 114 }
+        , _ in 0..1
+        , { name = "Jack"; true }
+    ]
+    .collect::<Vec<_>>();
+    assert_eq!(decl_with_mut_pattern_matching, ["Jack"]);
+
+    let decls_with_pattern_matching_and_let_else_p = iter_comp![
+        (num1, num2)
+        , let {
+            Some(num1) = Some(114) , else { panic!("Actually this panic shouldn't be called") };
+            Some(num2) = Some(514) , else { panic!("Actually this panic shouldn't be called") }
+        }
+    ]
+    .collect::<Vec<_>>();
+    assert_eq!(decls_with_pattern_matching_and_let_else_p, [(114, 514)]);
+
+    let guards = iter_comp![
+        (n, m)
+        ; n in shared_arr
+        , m in [0, 1, 2]
+        , let mut b3: i8 = 1
+        , let Some(num) = Some(114) else { panic!("Actually this panic shouldn't be called") }
+        , n != 1
+        , m != 2
+    ];
+    assert_eq!(guards.clone().collect::<Vec<_>>(), [(0, 0), (0, 1)]);
+    #[cfg(feature = "alloc")]
+    assert_eq!(
+        guards.collect::<Vec<_>>(),
+        comp![(n, m), n in [0], m in [0, 1]]
+    );
+
+    let infinite = iter_comp![n * n => n in 1.., n % 3 == 0];
+    assert_eq!(infinite.take(3).collect::<Vec<_>>(), [9, 36, 81]);
+
+    assert_eq!(evens_below(7).collect::<Vec<_>>(), [0, 2, 4, 6]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_top_level_guards() {
+    let enabled = false;
+    let xs = [1, 2, 3];
+
+    let guard_before_generator = comp![x, enabled, x in xs];
+    assert!(guard_before_generator.is_empty());
+
+    let guard_before_generator = comp![x, !enabled, x in xs, x != 2];
+    assert_eq!(guard_before_generator, [1, 3]);
+
+    let without_generator = comp![114, let n = 114, n > 100];
+    assert_eq!(without_generator, [114]);
+
+    let without_generator: Vec<i32> = comp![114, false];
+    assert!(without_generator.is_empty());
+
+    let iter_guard_before_generator = iter_comp![x, enabled, x in xs];
+    assert_eq!(iter_guard_before_generator.count(), 0);
+}
+
+#[cfg(feature = "lazy_comp")]
+#[test]
+fn test_lazy_top_level_guards() {
+    let enabled = false;
+    let xs = [1, 2, 3];
+
+    let guard_before_generator = lazy_comp![x, enabled, x in xs];
+    assert_eq!(guard_before_generator.into_iter().count(), 0);
+
+    let guard_before_generator = lazy_comp![x, !enabled, x in xs, x != 2]
+        .into_iter()
+        .collect::<Vec<_>>();
+    assert_eq!(guard_before_generator, [1, 3]);
+
+    let without_generator = lazy_comp![114, let n = 114, n > 100]
+        .into_iter()
+        .collect::<Vec<_>>();
+    assert_eq!(without_generator, [114]);
+}
+
+#[cfg(feature = "lazy_comp")]
+mod hygiene {
+    #[test]
+    fn test_lazy_comp_without_imports() {
+        let arr = list_comprehension::lazy_comp![n * 2, n in 0..3, n != 1];
+        assert_eq!(arr.into_iter().collect::<Vec<_>>(), [0, 4]);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_parallel_comp() {
+    let xs = [1, 2, 3, 4, 5, 6];
+    let ys = ["a", "b", "c"];
+
+    let shortest = comp![(x, y), zip { x in xs } { y in ys }];
+    assert_eq!(shortest, [(1, "a"), (2, "b"), (3, "c")]);
+
+    let with_guards = comp![
+        (x, y, z)
+        , zip { x in xs, x % 2 == 0 } { y <- ys } { for z in 0.., let w = z * 10, w != 10 }
+    ];
+    assert_eq!(with_guards, [(2, "a", 0), (4, "b", 2), (6, "c", 3)]);
+
+    let tuples_and_rest = comp![
+        (i, x, y)
+        , n in [0, 1]
+        , zip_eq { (i, x) in xs.iter().enumerate(), i < 3 } { y in ys }
+        , n + i != 2
+    ];
+    assert_eq!(
+        tuples_and_rest,
+        [(0, &1, "a"), (1, &2, "b"), (0, &1, "a"), (2, &3, "c")]
+    );
+
+    let iter = iter_comp![x * y, zip_eq { x in 1..4 } { y in [10, 20, 30] }];
+    assert_eq!(iter.collect::<Vec<_>>(), [10, 40, 90]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+#[should_panic(expected = "different lengths")]
+fn test_parallel_comp_strict() {
+    let _ = comp![(x, y), zip_eq { x in 0..3 } { y in 0..2 }];
+}
+
+#[cfg(feature = "lazy_comp")]
+#[test]
+fn test_lazy_parallel_comp() {
+    let lazy = lazy_comp![
+        (x, y)
+        , zip { x in 0.., x % 3 == 0 } { y in ["a", "b"] }
+    ]
+    .into_iter()
+    .collect::<Vec<_>>();
+    assert_eq!(lazy, [(0, "a"), (3, "b")]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_refutable_generator_patterns() {
+    let opts = [Some(1), None, Some(3)];
+    let results: [Result<i32, &str>; 3] = [Ok(1), Err("oops"), Ok(3)];
+
+    let somes = comp![x, Some(x) in opts];
+    assert_eq!(somes, [1, 3]);
+
+    let oks = comp![(x, e), Ok(x) in results, for Err(e) in results];
+    assert_eq!(oks, [(1, "oops"), (3, "oops")]);
+
+    let literal = comp![
+        name
+        , TestS { name, age: 114 } in [
+            TestS { name: "LiHua", age: 114 },
+            TestS { name: "Jack", age: 514 },
+        ]
+    ];
+    assert_eq!(literal, ["LiHua"]);
+
+    let iter_somes = iter_comp![x * y, Some(x) in opts, for Some(y) in opts];
+    assert_eq!(iter_somes.collect::<Vec<_>>(), [1, 3, 3, 9]);
+}
+
+#[cfg(feature = "lazy_comp")]
+#[test]
+fn test_lazy_refutable_generator_patterns() {
+    let opts = [Some(1), None, Some(3)];
+
+    let somes = lazy_comp![x, Some(x) in opts, for Some(y) in opts, x != y]
+        .into_iter()
+        .collect::<Vec<_>>();
+    assert_eq!(somes, [1, 3]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_try_comp() {
+    use std::num::ParseIntError;
+
+    fn parse(s: &str) -> Result<i32, ParseIntError> {
+        s.parse()
+    }
+
+    let ok: Result<Vec<i32>, ParseIntError> = try_comp![
+        a + b
+        , x in ["1", "2"]
+        , let a = parse(x)?
+        , y in ["10", "20"]
+        , let { b = parse(y)? }
+        , parse(x)? != 2 || b != 20
+    ];
+    assert_eq!(ok, Ok(vec![11, 21, 12]));
+
+    let mut evaluated = 0;
+    let err: Result<Vec<i32>, ParseIntError> = try_comp![
+        { evaluated += 1; parse(x)? }
+        , x in ["1", "x", "3"]
+    ];
+    assert!(err.is_err());
+    assert_eq!(evaluated, 2);
+
+    #[derive(Debug, PartialEq)]
+    struct MyError(String);
+
+    impl From<ParseIntError> for MyError {
+        fn from(e: ParseIntError) -> Self {
+            MyError(e.to_string())
+        }
+    }
+
+    let converted: Result<Vec<i32>, MyError> = try_comp![n, x in ["1", ""], let n = parse(x)?];
+    assert_eq!(
+        converted,
+        Err(MyError("cannot parse integer from empty string".into()))
+    );
+
+    let some: Option<Vec<i32>> = try_comp![x?; x in [Some(1), Some(2)]];
+    assert_eq!(some, Some(vec![1, 2]));
+
+    let none: Option<Vec<usize>> = try_comp![x => x in [1, 2], [0, 1].get(x)? == &1];
+    assert_eq!(none, None);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_early_termination() {
+    fn is_prime(n: u32) -> bool {
+        n > 1
+            && (2..n)
+                .take_while(|d| d * d <= n)
+                .all(|d| !n.is_multiple_of(d))
+    }
+
+    let primes = comp![n, n in 1.., is_prime(n), take 5];
+    assert_eq!(primes, [2, 3, 5, 7, 11]);
+
+    let mut checked = 0;
+    let primes = comp![n, n in 1.., take 3, { checked += 1; is_prime(n) }];
+    assert_eq!(primes, [2, 3, 5]);
+    assert_eq!(checked, 5);
+
+    let nothing: Vec<u32> = comp![n, take 0, n in 1..];
+    assert!(nothing.is_empty());
+
+    let pairs = comp![(x, y), x in 0.., y in 0..3, take 4];
+    assert_eq!(pairs, [(0, 0), (0, 1), (0, 2), (1, 0)]);
+
+    let take_while = comp![(x, y), x in 1..4, y in 1.., while y < x];
+    assert_eq!(take_while, [(2, 1), (3, 1), (3, 2)]);
+
+    let guarded_while = comp![(x, y), x in 0..3, y in 0.., let s = x + y, s % 2 == 0, while s < 4];
+    assert_eq!(guarded_while, [(0, 0), (0, 2), (1, 1), (2, 0)]);
+
+    let top_level_while: Vec<i32> = comp![x, while false, x in 0..];
+    assert!(top_level_while.is_empty());
+
+    let stop = comp![x, x in 0.., break if x * x > 20];
+    assert_eq!(stop, [0, 1, 2, 3, 4]);
+
+    let unconditional_stop = comp![
+        x
+        , x in [Some(1), Some(2), None, Some(3)]
+        , let Some(x) = x else { break }
+    ];
+    assert_eq!(unconditional_stop, [1, 2]);
+
+    let stop_after_guard: Vec<i32> = comp![x, x in 0.., x > 2, break];
+    assert!(stop_after_guard.is_empty());
+
+    let set: std::collections::BTreeSet<i32> = comp_into![x % 3, x in 0.., take 5];
+    assert_eq!(set, std::collections::BTreeSet::from([0, 1, 2]));
+}
+
+#[cfg(feature = "lazy_comp")]
+#[test]
+fn test_lazy_early_termination() {
+    let lazy = lazy_comp![(x, y), x in 0.., y in 0.., while y < x, take 4]
+        .into_iter()
+        .collect::<Vec<_>>();
+    assert_eq!(lazy, [(1, 0), (2, 0), (2, 1), (3, 0)]);
+
+    let lazy = lazy_comp![x, x in 0.., break if x == 3]
+        .into_iter()
+        .collect::<Vec<_>>();
+    assert_eq!(lazy, [0, 1, 2]);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_comp() {
+    let shared_arr = [0, 1];
+
+    let first_generator_syntax = par_comp![
+        (a1, a2)
+        , a1 <- shared_arr
+        , a2 <- [2, 3]
+    ];
+    assert_eq!(first_generator_syntax, [(0, 2), (0, 3), (1, 2), (1, 3)]);
+
+    let ordered = par_comp![
+        (x, y)
+        ; x in 0..200
+        , let s = x * x
+        , y in 0..x
+        , (s + y) % 7 == 0
+    ];
+    assert_eq!(
+        ordered,
+        comp![(x, y); x in 0..200, let s = x * x, y in 0..x, (s + y) % 7 == 0]
+    );
+
+    let refutable = par_comp![x => Some(x) in vec![Some(1), None, Some(3)]];
+    assert_eq!(refutable, [1, 3]);
+
+    let people = vec![
+        TestS {
+            name: "LiHua",
+            age: 114,
+        },
+        TestS {
+            name: "Jack",
+            age: 514,
+        },
+    ];
+    let names = par_comp![name, TestS { name, .. } in &people, n in 0..2, n == 1];
+    assert_eq!(names, [&"LiHua", &"Jack"]);
+}
+
+#[cfg(feature = "stream_comp")]
+#[test]
+fn test_stream_comp() {
+    use futures::{executor::block_on, stream, StreamExt};
+
+    async fn double(n: i32) -> i32 {
+        n * 2
+    }
+
+    async fn is_odd(n: i32) -> bool {
+        n % 2 == 1
+    }
+
+    let arr = stream_comp![
+        (x, y, d)
+        , x in stream::iter(vec![0, 1, 2]).await_each
+        , y <- [10, 20]
+        , let d = double(x).await
+        , is_odd(x).await
+    ];
+    assert_eq!(block_on(arr.collect::<Vec<_>>()), [(1, 10, 2), (1, 20, 2)]);
+
+    let nested = stream_comp![
+        double(x + y).await
+        ; for x in stream::iter(0..3).await_each
+        , y <- stream::iter([10, 20]).await_each
+        , take 4
+    ];
+    assert_eq!(block_on(nested.collect::<Vec<_>>()), [20, 40, 22, 42]);
+
+    let refutable = stream_comp![
+        x => Some(x) in stream::iter(vec![Some(1), None, Some(3)]).await_each
+    ];
+    assert_eq!(block_on(refutable.collect::<Vec<_>>()), [1, 3]);
+
+    // a long qualifier doesn't hit the recursion limit
+    let long_guard = stream_comp![
+        x
+        , x in stream::iter(0..200).await_each
+        , x == 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1
+            + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1
+            + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1
+            + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1
+            + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 1
+    ];
+    assert_eq!(block_on(long_guard.collect::<Vec<_>>()), [100]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_qualifier_parser() {
+    let people = [
+        TestS {
+            name: "LiHua",
+            age: 114,
+        },
+        TestS {
+            name: "Jack",
+            age: 514,
+        },
+    ];
+
+    // no `for` needed in front of a pattern that looks like an expression
+    let names = comp![name, TestS { name, .. } in &people];
+    assert_eq!(names, [&"LiHua", &"Jack"]);
+
+    let ages = iter_comp![age, &TestS { age, .. } in &people, age > 200].collect::<Vec<_>>();
+    assert_eq!(ages, [514]);
+
+    // `<` followed by `-` is a comparison unless the tokens are joined
+    let negatives = comp![x, x in -3..3, x < -1];
+    assert_eq!(negatives, [-3, -2]);
+
+    // `take` is only a qualifier when it's followed by an expression
+    let take = true;
+    let arr = comp![x, x in 0..5, take, take 2];
+    assert_eq!(arr, [0, 1]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_spread_output() {
+    let xs = [1, 2, 3];
+
+    let arr = comp![..[x, -x], x in xs];
+    assert_eq!(arr, [1, -1, 2, -2, 3, -3]);
+
+    let inner = vec![vec![0], vec![], vec![1, 2]];
+    let flat = comp![..v.clone(); v in &inner, x in 0..2, x == 1];
+    assert_eq!(flat, [0, 1, 2]);
+
+    let counts = comp![..std::iter::repeat_n(x, x), x in 0..4, take 2];
+    assert_eq!(counts, [1, 2]);
+
+    let s: String = comp_into![..[c, c] => c in "abc".chars(), take 3];
+    assert_eq!(s, "aab");
+
+    let s: String = comp_into![..[c, c] => c in "ab".chars()];
+    assert_eq!(s, "aabb");
+
+    let pairs = iter_comp![..[(x, y), (y, x)], x in 0..2, y in 2..3].collect::<Vec<_>>();
+    assert_eq!(pairs, [(0, 2), (2, 0), (1, 2), (2, 1)]);
+}
+
+#[cfg(feature = "lazy_comp")]
+#[test]
+fn test_lazy_spread_output() {
+    let words = ["ab", "", "cde"];
+
+    let chars = lazy_comp![..w.chars(), w in words, !w.is_empty()];
+    assert_eq!(chars.into_iter().collect::<String>(), "abcde");
+
+    let naturals = lazy_comp![..[n, n], n in 0..];
+    assert_eq!(
+        naturals.into_iter().take(5).collect::<Vec<_>>(),
+        [0, 0, 1, 1, 2]
+    );
+    let taken = lazy_comp![..[n, n], n in 0.., take 3];
+    assert_eq!(taken.into_iter().collect::<Vec<_>>(), [0, 0, 1]);
+}
+
+#[test]
+fn test_for_comp() {
+    let mut log = Vec::new();
+    for_comp![
+        { log.push(format!("{a}{b}")); }
+        ; let { a = 'x'; mut b = 0 }
+        , _ in 0..3
+        , let _ = { b += 1; }
+    ];
+    assert_eq!(log, ["x1", "x2", "x3"]);
+
+    let mut pairs = Vec::new();
+    for_comp![
+        {
+            pairs.push((x, y));
+            if x == y {
+                pairs.push((y, x));
+            }
+        }
+        => x in 0..3
+        , y in x..3
+        , x + y < 3
+        , take 4
+    ];
+    assert_eq!(pairs, [(0, 0), (0, 0), (0, 1), (0, 2), (1, 1), (1, 1)]);
+
+    let mut total = 0;
+    for_comp![{ total += v }, Some(v) in [Some(1), None, Some(2)]];
+    assert_eq!(total, 3);
+}
+
+#[test]
+fn test_aggregate_comp() {
+    let a = [1, 2, 3];
+    let b = [10, 20];
+
+    let sum: i32 = comp![sum: x * y, x in a, y in b];
+    assert_eq!(sum, 180);
+    let empty_sum: i32 = comp![sum: x, x in a, x > 3];
+    assert_eq!(empty_sum, 0);
+    let product: u64 = comp![product: x as u64; x in 1..=10, take 5];
+    assert_eq!(product, 120);
+    let float_sum: f64 = comp![sum: x as f64 / 2.0 => x in a];
+    assert_eq!(float_sum, 3.0);
+    let ref_sum: i32 = comp![sum: x, x in &a];
+    assert_eq!(ref_sum, 6);
+    let ref_product: i32 = comp![product: x, x in &b];
+    assert_eq!(ref_product, 200);
+
+    assert_eq!(comp![count: x in a, y in b, x * y > 20], 3);
+    assert_eq!(comp![count: x in a, break if x == 2], 1);
+
+    assert_eq!(comp![min: x - y, x in a, y in b], Some(-19));
+    assert_eq!(comp![max: x - y, x in a, y in b], Some(-7));
+    assert_eq!(comp![max: x, x in a, x > 3], None);
+
+    let people = [
+        TestS {
+            name: "LiHua",
+            age: 114,
+        },
+        TestS {
+            name: "Jack",
+            age: 514,
+        },
+        TestS {
+            name: "Tom",
+            age: 114,
+        },
+    ];
+    let youngest = comp![min_by_key(|p| p.age): p, p in &people];
+    assert_eq!(youngest.map(|p| p.name), Some("LiHua"));
+    let oldest = comp![max_by_key(|p| p.age): p, p in &people, p.name != "Jack"];
+    assert_eq!(oldest.map(|p| p.name), Some("Tom"));
+    let key = |(_, n): &(i32, i32)| n % 4;
+    assert_eq!(
+        comp![min_by_key(key): (x, y), x in a, let y = x * x],
+        Some((2, 4))
+    );
+
+    let mut checked = Vec::new();
+    let any = comp![any: { checked.push(x); x % 2 == 0 }, x in 1..];
+    assert!(any);
+    assert_eq!(checked, [1, 2]);
+    assert!(comp![all: x > 0, x in a]);
+    assert!(!comp![all: x < 2, x in 0..]);
+    assert!(!comp![any: x > 9, x in a]);
+
+    let joined = comp![fold(String::new(), |acc, s| acc + s): s, p in &people, let s = p.name];
+    assert_eq!(joined, "LiHuaJackTom");
+    let mut calls = 0;
+    let mut max_len = |acc: usize, s: &str| {
+        calls += 1;
+        acc.max(s.len())
+    };
+    assert_eq!(comp![fold(0, max_len): p.name, p in &people], 5);
+    assert_eq!(calls, 3);
+}
+
+#[test]
+#[deny(unreachable_code)]
+fn test_find_comp() {
+    let words = ["apple", "banana", "cherry"];
+
+    let first = find_comp![(w, c), w in words, c in w.chars(), c == 'n'];
+    assert_eq!(first, Some(("banana", 'n')));
+
+    let mut visited = 0;
+    let found = find_comp![
+        x * y
+        ; x in 1..
+        , y in 1..=x
+        , let _ = visited += 1
+        , x * y == 12
+    ];
+    assert_eq!(found, Some(12));
+    assert_eq!(visited, 1 + 2 + 3 + 3);
+
+    let none = find_comp![x => x in 0..10, x > 10];
+    assert_eq!(none, None);
+
+    let people = [
+        TestS {
+            name: "LiHua",
+            age: 114,
+        },
+        TestS {
+            name: "Jack",
+            age: 514,
+        },
+    ];
+    let jack = find_comp![p, p in &people, p.age > 200];
+    assert_eq!(jack.map(|p| p.name), Some("Jack"));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_join() {
+    #[derive(Debug, Clone, PartialEq)]
+    struct Order {
+        id: u32,
+        cid: u32,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Customer {
+        id: u32,
+        name: &'static str,
+    }
+
+    let orders = vec![
+        Order { id: 1, cid: 2 },
+        Order { id: 2, cid: 1 },
+        Order { id: 3, cid: 9 },
+        Order { id: 4, cid: 2 },
+    ];
+    let customers = vec![
+        Customer {
+            id: 1,
+            name: "LiHua",
+        },
+        Customer {
+            id: 2,
+            name: "Jack",
+        },
+        Customer {
+            id: 2,
+            name: "Jack's twin",
+        },
+    ];
+
+    let nested = comp![(o.id, c.name), o in &orders, c in &customers, o.cid == c.id];
+    let joined = comp![(o.id, c.name), o in &orders, join c in &customers on o.cid == c.id];
+    assert_eq!(joined, nested);
+    assert_eq!(
+        joined,
+        [
+            (1, "Jack"),
+            (1, "Jack's twin"),
+            (2, "LiHua"),
+            (4, "Jack"),
+            (4, "Jack's twin")
+        ]
+    );
+
+    // the sides of `==` may be swapped, and the joined generator may be consumed by value
+    let joined = comp![
+        (o.id, c.name)
+        ; o in &orders
+        , join c in customers.clone() on c.id == o.cid
+        , o.id > 1
+    ];
+    assert_eq!(joined, [(2, "LiHua"), (4, "Jack"), (4, "Jack's twin")]);
+
+    // refutable patterns skip the items that don't match
+    let names = [Some((1, "LiHua")), None, Some((2, "Jack"))];
+    let joined = comp![(o.id, name), o in &orders, join Some((id, name)) in names on id == o.cid];
+    assert_eq!(joined, [(1, "Jack"), (2, "LiHua"), (4, "Jack")]);
+
+    let count = comp![count: o in &orders, join c in &customers on o.cid == c.id];
+    assert_eq!(count, 5);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_transform_comp() {
+    let people = [
+        TestS {
+            name: "LiHua",
+            age: 114,
+        },
+        TestS {
+            name: "Jack",
+            age: 514,
+        },
+        TestS {
+            name: "Rose",
+            age: 19,
+        },
+    ];
+
+    let sorted = comp![p.name, p in &people, sort by p.age];
+    assert_eq!(sorted, ["Rose", "LiHua", "Jack"]);
+
+    // the sort is stable, and the qualifiers after it see the sorted bindings
+    let sorted = comp![
+        (x, y)
+        ; x in 0..3
+        , y in ["a", "b"]
+        , sort by std::cmp::Reverse(x)
+        , x != 1
+        , take 3
+    ];
+    assert_eq!(sorted, [(2, "a"), (2, "b"), (0, "a")]);
+
+    // the variables are references in the key
+    let words = vec![String::from("ccc"), String::from("a"), String::from("bb")];
+    let sorted = comp![w, w in words.clone(), let n = w.len(), sort by n];
+    assert_eq!(sorted, ["a", "bb", "ccc"]);
+    let sorted = comp![w, w in words, sort by w];
+    assert_eq!(sorted, ["a", "bb", "ccc"]);
+
+    // every variable becomes the `Vec` of its values in the group
+    let sales = [
+        ("east", 3),
+        ("west", 5),
+        ("east", 4),
+        ("north", 1),
+        ("west", 2),
+    ];
+    let report = comp![
+        (region[0], amount.iter().sum::<i32>())
+        ; (region, amount) in sales
+        , group by region
+        , amount.len() > 1
+    ];
+    assert_eq!(report, [("east", 7), ("west", 7)]);
+
+    let groups = comp![(k[0], v), (k, v) in sales, sort by v, group by k];
+    assert_eq!(
+        groups,
+        [
+            ("east", vec![3, 4]),
+            ("north", vec![1]),
+            ("west", vec![2, 5])
+        ]
+    );
+
+    let groups = comp![(x, y), x in 0..6, let y = x * x, group by x % 3];
+    assert_eq!(
+        groups,
+        [
+            (vec![0, 3], vec![0, 9]),
+            (vec![1, 4], vec![1, 16]),
+            (vec![2, 5], vec![4, 25])
+        ]
+    );
+
+    let dropped = comp![n, n in 1.., n % 2 == 0, drop 2, take 3];
+    assert_eq!(dropped, [6, 8, 10]);
+
+    let page = comp![p.name, p in &people, sort by p.name, drop 1, take 1];
+    assert_eq!(page, ["LiHua"]);
+
+    let total: i32 = comp![sum: n, n in 0..10, sort by -n, drop 7];
+    assert_eq!(total, 3);
+}
+
+#[cfg(feature = "lazy_comp")]
+#[test]
+fn test_fair_lazy_comp() {
+    // every pair is reached, although both generators are infinite
+    let pairs = lazy_comp![fair: (x, y), x in 0.., y in 0..];
+    let mut it = pairs.into_iter();
+    assert_eq!(it.position(|pair| pair == (3, 5)), Some(36 + 3));
+
+    // the guards and `let`s see the bindings in the diagonal order
+    let triples = lazy_comp![
+        fair: (a, b, c)
+        ; a in 1..
+        , b in 1..
+        , let c2 = a * a + b * b
+        , a < b
+        , c in 1..
+        , c * c == c2
+    ];
+    let first: Vec<(u32, u32, u32)> = triples.into_iter().take(2).collect();
+    assert_eq!(first, [(3, 4, 5), (6, 8, 10)]);
+
+    // finite generators end the comprehension, with every pair visited once
+    let mut pairs: Vec<(i32, char)> = lazy_comp![fair: (x, c), x in [1, 2, 3], c in "ab".chars()]
+        .into_iter()
+        .collect();
+    assert_eq!(
+        pairs,
+        [(1, 'a'), (1, 'b'), (2, 'a'), (2, 'b'), (3, 'a'), (3, 'b')]
+    );
+    pairs.sort();
+    assert_eq!(pairs, comp![(x, c), x in [1, 2, 3], c in "ab".chars()]);
+
+    let empty: Vec<(i32, i32)> = lazy_comp![fair: (x, y), x in 0.., y in []]
+        .into_iter()
+        .collect();
+    assert!(empty.is_empty());
+
+    let spread: Vec<i32> =
+        lazy_comp![fair: ..[x, y], Some(x) in [None, Some(1)].into_iter().cycle(), y in 10..]
+            .into_iter()
+            .take(4)
+            .collect();
+    assert_eq!(spread, [1, 10, 1, 11]);
+}
+
+#[cfg(feature = "lazy_comp")]
+#[test]
+fn test_lazy_comp_iter() {
+    let words = vec![String::from("hello"), String::from("world")];
+    let ws = &words;
+    let n = 2;
+
+    let lazy = lazy_comp![w.len() * i, w in ws, i in 1..=n];
+    assert_eq!(lazy.iter().collect::<Vec<_>>(), [5, 10, 5, 10]);
+    assert_eq!(lazy.iter().sum::<usize>(), 30);
+
+    let mut replayed = Vec::new();
+    for _ in 0..2 {
+        for len in &lazy {
+            replayed.push(len);
+        }
+    }
+    assert_eq!(replayed, [5, 10, 5, 10, 5, 10, 5, 10]);
+
+    // it can still be consumed afterwards
+    assert_eq!(lazy.into_iter().count(), 4);
+    assert_eq!(words.len(), 2);
+
+    let fair = lazy_comp![fair: (x, y), x in 0.., y in 0..];
+    assert_eq!(fair.iter().nth(2), Some((1, 0)));
+    assert_eq!(fair.iter().nth(2), Some((1, 0)));
+
+    // an owned collection is cloned for every iteration
+    let owned = vec![String::from("a"), String::from("bc")];
+    let lens = lazy_comp![w.len(), w in owned];
+    assert_eq!(lens.iter().collect::<Vec<_>>(), [1, 2]);
+    assert_eq!((&lens).into_iter().count(), 2);
+
+    let lens = lazy_comp![ref: w.len(), w in words.iter()];
+    assert_eq!(lens.iter().collect::<Vec<_>>(), [5, 5]);
+    assert_eq!((&lens).into_iter().count(), 2);
+}
+
+#[cfg(feature = "lazy_comp")]
+#[test]
+fn test_borrowing_lazy_comp() {
+    let big_vec: Vec<i32> = (0..10).collect();
+    let evens = lazy_comp![ref: x * 10, x in &big_vec, x % 2 == 0];
+    assert_eq!(evens.iter().collect::<Vec<_>>(), [0, 20, 40, 60, 80]);
+    assert_eq!(big_vec.len(), 10);
+    assert_eq!(evens.iter().count(), 5);
+
+    let words = [String::from("a"), String::from("abc"), String::from("abcd")];
+    let long = lazy_comp![ref: w, w in words.iter(), w.len() > 2];
+    assert_eq!(long.into_iter().collect::<Vec<_>>(), ["abc", "abcd"]);
+    assert_eq!(words.len(), 3);
+
+    // the captures that are consumed are still moved
+    let owned = vec![1, 2];
+    let moved = lazy_comp![ref: ..[x, x], x in owned];
+    assert_eq!(moved.into_iter().collect::<Vec<_>>(), [1, 1, 2, 2]);
+
+    let pairs = lazy_comp![ref fair: (*x, y), x in &big_vec, y in 0..];
+    assert_eq!(
+        pairs.iter().take(3).collect::<Vec<_>>(),
+        [(0, 0), (0, 1), (1, 0)]
+    );
+    assert_eq!(big_vec[9], 9);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_let_block_statements() {
+    let pairs = [(1u8, Some(2u8)), (3, None), (5, Some(6))];
+
+    let native_else = comp![
+        (a, b, c)
+        ; p in pairs
+        , let {
+            (a, opt): (u8, Option<u8>) = p;
+            Some(b) = opt else { continue };
+            c: u16 = (a as u16) * (b as u16);
+        }
+    ];
+    assert_eq!(native_else, [(1, 2, 2), (5, 6, 30)]);
+
+    let mut_and_ref = comp![
+        (total, *first)
+        ; v in [vec![1, 2], vec![3]]
+        , let mut {
+            total = 0;
+            ref first = v[0];
+            (x, y): (i32, i32) = (v.len() as i32, 10);
+            _ = { total += x * y; };
+        }
+    ];
+    assert_eq!(mut_and_ref, [(20, 1), (10, 3)]);
+
+    let ref_mut = comp![
+        s
+        ; word in ["a", "b"]
+        , let { mut s = String::from(word); ref mut r = s.clone() }
+        , let () = r.push('!')
+        , let () = s.push_str(r)
+    ];
+    assert_eq!(ref_mut, ["aa!", "bb!"]);
+
+    let iter: Vec<u8> = iter_comp![
+        a + b
+        , p in pairs
+        , let { (a, opt): (u8, Option<u8>) = p; b = opt.unwrap_or(0) }
+    ]
+    .collect();
+    assert_eq!(iter, [3, 3, 11]);
+
+    let empty = comp![x, x in [1, 2], let {}, let mut {}];
+    assert_eq!(empty, [1, 2]);
+}
+
+#[cfg(feature = "lazy_comp")]
+#[test]
+fn test_lazy_let_block_statements() {
+    let pairs = [(1u8, Some(2u8)), (3, None), (5, Some(6))];
+
+    let lazy = lazy_comp![
+        (a, b)
+        ; p in pairs
+        , let { (a, opt): (u8, Option<u8>) = p; Some(b) = opt else { continue } }
+    ];
+    assert_eq!(lazy.into_iter().collect::<Vec<_>>(), [(1, 2), (5, 6)]);
+}
+
+#[cfg(feature = "lazy_comp")]
+#[test]
+fn test_lazy_comp_backends() {
+    let xs = [1, 2, 3];
+
+    let rc = lazy_comp![rc: x * 10, x in xs, x != 2];
+    assert_eq!(rc.clone().into_iter().collect::<Vec<_>>(), [10, 30]);
+    assert_eq!(rc.into_iter().sum::<i32>(), 40);
+
+    let rc = lazy_comp![ref rc fair: (*x, y), x in &xs, y in 0..];
+    assert_eq!(
+        rc.iter().take(3).collect::<Vec<_>>(),
+        [(1, 0), (1, 1), (2, 0)]
+    );
+    assert_eq!((&rc).into_iter().nth(4), Some((2, 1)));
+
+    let_lazy_comp!(stack = [..[x, -x], x in xs, x > 1]);
+    assert_eq!(stack.into_iter().collect::<Vec<_>>(), [2, -2, 3, -3]);
+
+    let_lazy_comp!(pairs = [ref fair: (*x, y), x in &xs, y in 0..]);
+    let mut first = Vec::new();
+    for pair in &mut pairs {
+        first.push(pair);
+        if first.len() == 3 {
+            break;
+        }
+    }
+    assert_eq!(first, [(1, 0), (1, 1), (2, 0)]);
+    assert_eq!(pairs.into_iter().next(), Some((1, 2)));
+    assert_eq!(xs.len(), 3);
+}
+
+#[test]
+fn test_fill_comp() {
+    let mut slots = [0; 4];
+    let mut buf = SliceBuf::new(&mut slots);
+    fill_comp![stop(buf): n * n, n in 1.., n % 2 == 1];
+    assert!(buf.is_full());
+    assert_eq!(*buf, [1, 9, 25, 49]);
+
+    let mut slots = [(0, 0); 8];
+    let mut buf = SliceBuf::new(&mut slots);
+    let res = fill_comp![err(&mut buf): (x, y) => x in 0..2, y in 0..2, x != y];
+    assert_eq!(res, Ok(()));
+    assert_eq!(*buf, [(0, 1), (1, 0)]);
+
+    let mut slots = [""; 2];
+    let mut buf = SliceBuf::new(&mut slots);
+    let res = fill_comp![err(buf): s; s in ["a", "b", "c", "d"]];
+    assert_eq!(res.unwrap_err().element(), "c");
+    assert_eq!(buf.into_slice(), ["a", "b"]);
+
+    let mut slots = [0; 3];
+    let mut buf = SliceBuf::new(&mut slots);
+    fill_comp![panic(buf): x, x in [1, 2, 3]];
+    assert_eq!(buf.len(), 3);
+
+    // the buffer is filled by the last output, so the generator isn't searched further
+    let mut evaluated = 0;
+    let mut slots = [0; 4];
+    let mut buf = SliceBuf::new(&mut slots);
+    fill_comp![stop(buf): { evaluated += 1; n }, n in 1.., n <= 4];
+    assert_eq!(*buf, [1, 2, 3, 4]);
+    assert_eq!(evaluated, 4);
+
+    let mut buf = SliceBuf::new(&mut []);
+    fill_comp![stop(buf): n, n in 1.., n < 0];
+    assert!(buf.is_empty());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_fill_comp_vec() {
+    let mut vec = Vec::with_capacity(2);
+    // `with_capacity` may allocate more than asked for
+    let cap = vec.capacity();
+    let res = fill_comp![err(vec): x, x in 0..];
+    assert_eq!(res, Err(CapacityError::new(cap)));
+    assert_eq!(vec, (0..cap).collect::<Vec<_>>());
+
+    let mut vec = Vec::with_capacity(2);
+    fill_comp![stop(vec): x, x in 0..];
+    assert_eq!(vec.len(), vec.capacity());
+}
+
+#[cfg(feature = "arrayvec")]
+#[test]
+fn test_fill_comp_arrayvec() {
+    let mut arr = arrayvec::ArrayVec::<i32, 3>::new();
+    fill_comp![stop(arr): x * x, x in 1..];
+    assert_eq!(arr.as_slice(), [1, 4, 9]);
+
+    let mut arr = arrayvec::ArrayVec::<i32, 2>::new();
+    let res = fill_comp![err(arr): x, x in 0..5];
+    assert_eq!(res, Err(CapacityError::new(2)));
+}
+
+#[cfg(feature = "heapless")]
+#[test]
+fn test_fill_comp_heapless() {
+    let mut vec = heapless::Vec::<i32, 3>::new();
+    fill_comp![stop(vec): x * x, x in 1..];
+    assert_eq!(vec, [1, 4, 9]);
+
+    let mut vec = heapless::Vec::<i32, 2>::new();
+    let res = fill_comp![err(vec): x, x in 0..5];
+    assert_eq!(res, Err(CapacityError::new(2)));
+}
+
+#[test]
+#[should_panic(expected = "is full")]
+fn test_fill_comp_overflow() {
+    let mut slots = [0; 1];
+    let mut buf = SliceBuf::new(&mut slots);
+    fill_comp![panic(buf): x, x in 0..2];
+}